@@ -0,0 +1,19 @@
+use std::env;
+use std::path::PathBuf;
+
+// The header is generated into OUT_DIR, the committed include/mlaa.h is
+// checked against it by the tests.
+fn main() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).unwrap())
+        .generate()
+        .expect("Unable to generate the C header")
+        .write_to_file(out_dir.join("mlaa.h"));
+}
@@ -0,0 +1,151 @@
+/* Generated by cbindgen from crates/mlaa_capi, do not edit. */
+
+#ifndef MLAA_H
+#define MLAA_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum MlaaBlendSpace {
+  MLAA_BLEND_SPACE_SRGB = 0,
+  MLAA_BLEND_SPACE_LINEAR = 1,
+  MLAA_BLEND_SPACE_OKLAB = 2,
+  MLAA_BLEND_SPACE_CIELAB = 3,
+} MlaaBlendSpace;
+
+typedef enum MlaaBrightnessMetric {
+  MLAA_BRIGHTNESS_METRIC_REC601_LUMA = 0,
+  MLAA_BRIGHTNESS_METRIC_REC709_LUMA = 1,
+  MLAA_BRIGHTNESS_METRIC_LINEAR_LUMINANCE = 2,
+  MLAA_BRIGHTNESS_METRIC_CIE_LIGHTNESS = 3,
+  MLAA_BRIGHTNESS_METRIC_MAX_CHANNEL = 4,
+  MLAA_BRIGHTNESS_METRIC_ALPHA_WEIGHTED_LUMA = 5,
+} MlaaBrightnessMetric;
+
+typedef enum MlaaFeatureKind {
+  MLAA_FEATURE_KIND_VERTICAL_GRADIENT = 0,
+  MLAA_FEATURE_KIND_HORIZONTAL_GRADIENT = 1,
+  MLAA_FEATURE_KIND_CORNER = 2,
+} MlaaFeatureKind;
+
+typedef enum MlaaPixelFormat {
+  MLAA_PIXEL_FORMAT_RGBA8 = 0,
+  MLAA_PIXEL_FORMAT_RGBA16 = 1,
+  MLAA_PIXEL_FORMAT_RGBA32_F = 2,
+} MlaaPixelFormat;
+
+typedef enum MlaaStatus {
+  MLAA_STATUS_OK = 0,
+  MLAA_STATUS_NULL_POINTER = 1,
+  MLAA_STATUS_INVALID_OPTIONS = 2,
+  MLAA_STATUS_INVALID_IMAGE = 3,
+  MLAA_STATUS_UNKNOWN_PRESET = 4,
+  MLAA_STATUS_PANIC = 5,
+} MlaaStatus;
+
+typedef struct MlaaOptions {
+  bool vertical_smoothing;
+  bool horizontal_smoothing;
+  bool corner_smoothing;
+  bool strict_mode;
+  float seam_split_position;
+  bool seam_brightness_balance;
+  uint32_t blend_space;
+  uint32_t brightness_metric;
+} MlaaOptions;
+
+/**
+ * Non-premultiplied RGBA pixels, `stride` is the distance between the rows in
+ * bytes. The pixel data has to be aligned to the size of a channel.
+ * `pixel_format` holds an `MlaaPixelFormat` value.
+ *
+ * RGBA8 and RGBA16 pixels are sRGB-encoded, RGBA32F pixels hold linear light
+ * and may exceed 1.0. Alpha is linear in every format.
+ */
+typedef struct MlaaImage {
+  void *pixels;
+  uint32_t width;
+  uint32_t height;
+  size_t stride;
+  uint32_t pixel_format;
+} MlaaImage;
+
+typedef struct MlaaFeature {
+  enum MlaaFeatureKind kind;
+  float x;
+  float y;
+  float length;
+  float colors[2][4];
+} MlaaFeature;
+
+typedef void (*MlaaFeatureCallback)(const struct MlaaFeature *feature, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+const char *mlaa_status_message(uint32_t status);
+
+/**
+ * # Safety
+ *
+ * `options` must be NULL or point to writable memory for an `MlaaOptions`.
+ */
+enum MlaaStatus mlaa_options_default(struct MlaaOptions *options);
+
+/**
+ * # Safety
+ *
+ * `name` must be NULL or a NUL-terminated string, `options` must be NULL or
+ * point to writable memory for an `MlaaOptions`.
+ */
+enum MlaaStatus mlaa_options_preset(const char *name, struct MlaaOptions *options);
+
+/**
+ * # Safety
+ *
+ * `image` must describe a valid pixel buffer, `options` must be NULL or point
+ * to an `MlaaOptions`. The callback must not unwind.
+ */
+enum MlaaStatus mlaa_features_callback(const struct MlaaImage *image,
+                                       const struct MlaaOptions *options,
+                                       MlaaFeatureCallback callback,
+                                       void *user_data);
+
+/**
+ * # Safety
+ *
+ * `image` must describe a valid pixel buffer, `options` must be NULL or point
+ * to an `MlaaOptions`. The returned array has to be released with
+ * `mlaa_features_free`.
+ */
+enum MlaaStatus mlaa_features_array(const struct MlaaImage *image,
+                                    const struct MlaaOptions *options,
+                                    struct MlaaFeature **features,
+                                    size_t *feature_count);
+
+/**
+ * # Safety
+ *
+ * `features` and `feature_count` must come from a single
+ * `mlaa_features_array` call, the array must not be freed twice.
+ */
+void mlaa_features_free(struct MlaaFeature *features, size_t feature_count);
+
+/**
+ * # Safety
+ *
+ * `image` must describe a valid, writable pixel buffer, `options` must be
+ * NULL or point to an `MlaaOptions`.
+ */
+enum MlaaStatus mlaa_process_in_place(const struct MlaaImage *image,
+                                      const struct MlaaOptions *options);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* MLAA_H */
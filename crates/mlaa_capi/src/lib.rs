@@ -0,0 +1,587 @@
+use std::ffi::{c_char, c_void, CStr};
+use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe};
+use std::{mem, ptr, slice};
+
+use mlaa_impl::{
+    mlaa_linear_pixels_features, mlaa_linear_pixels_in_place, mlaa_pixels_features, mlaa_pixels_in_place,
+    rgba_to_samples, samples_to_rgba, Channel, MlaaFeature as ImplFeature, MlaaOptions as ImplOptions, MlaaPixels,
+    MlaaPixelsMut,
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MlaaStatus {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidOptions = 2,
+    InvalidImage = 3,
+    UnknownPreset = 4,
+    Panic = 5,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub enum MlaaPixelFormat {
+    Rgba8 = 0,
+    Rgba16 = 1,
+    Rgba32F = 2,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub enum MlaaBlendSpace {
+    Srgb = 0,
+    Linear = 1,
+    Oklab = 2,
+    Cielab = 3,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub enum MlaaBrightnessMetric {
+    Rec601Luma = 0,
+    Rec709Luma = 1,
+    LinearLuminance = 2,
+    CieLightness = 3,
+    MaxChannel = 4,
+    AlphaWeightedLuma = 5,
+}
+
+// The enums are passed in from C as plain integers, `blend_space` holds an
+// `MlaaBlendSpace` and `brightness_metric` an `MlaaBrightnessMetric` value.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct MlaaOptions {
+    pub vertical_smoothing: bool,
+    pub horizontal_smoothing: bool,
+    pub corner_smoothing: bool,
+
+    pub strict_mode: bool,
+    pub seam_split_position: f32,
+    pub seam_brightness_balance: bool,
+
+    pub blend_space: u32,
+    pub brightness_metric: u32,
+}
+
+/// Non-premultiplied RGBA pixels, `stride` is the distance between the rows in
+/// bytes. The pixel data has to be aligned to the size of a channel.
+/// `pixel_format` holds an `MlaaPixelFormat` value.
+///
+/// RGBA8 and RGBA16 pixels are sRGB-encoded, RGBA32F pixels hold linear light
+/// and may exceed 1.0. Alpha is linear in every format.
+#[repr(C)]
+pub struct MlaaImage {
+    pub pixels: *mut c_void,
+    pub width: u32,
+    pub height: u32,
+    pub stride: usize,
+    pub pixel_format: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub enum MlaaFeatureKind {
+    VerticalGradient = 0,
+    HorizontalGradient = 1,
+    Corner = 2,
+}
+
+// `length` is the height of vertical and the width of horizontal gradients,
+// it is zero for corners. Colors are RGBA floats in the encoding of the image
+// pixels.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct MlaaFeature {
+    pub kind: MlaaFeatureKind,
+    pub x: f32,
+    pub y: f32,
+    pub length: f32,
+    pub colors: [[f32; 4]; 2],
+}
+
+// Out of range values must not be turned into the enums, so the integers are
+// checked against the variants.
+macro_rules! impl_try_from_u32 {
+    ($name:ident, $error:expr, [$($variant:ident),*]) => {
+        impl TryFrom<u32> for $name {
+            type Error = MlaaStatus;
+
+            fn try_from(value: u32) -> Result<Self, Self::Error> {
+                [$($name::$variant),*]
+                    .into_iter()
+                    .find(|&variant| variant as u32 == value)
+                    .ok_or($error)
+            }
+        }
+    };
+}
+
+impl_try_from_u32!(
+    MlaaStatus,
+    MlaaStatus::InvalidOptions,
+    [Ok, NullPointer, InvalidOptions, InvalidImage, UnknownPreset, Panic]
+);
+impl_try_from_u32!(MlaaPixelFormat, MlaaStatus::InvalidImage, [Rgba8, Rgba16, Rgba32F]);
+impl_try_from_u32!(
+    MlaaBlendSpace,
+    MlaaStatus::InvalidOptions,
+    [Srgb, Linear, Oklab, Cielab]
+);
+impl_try_from_u32!(
+    MlaaBrightnessMetric,
+    MlaaStatus::InvalidOptions,
+    [
+        Rec601Luma,
+        Rec709Luma,
+        LinearLuminance,
+        CieLightness,
+        MaxChannel,
+        AlphaWeightedLuma
+    ]
+);
+
+pub type MlaaFeatureCallback = Option<unsafe extern "C" fn(feature: *const MlaaFeature, user_data: *mut c_void)>;
+
+impl From<&ImplOptions> for MlaaOptions {
+    fn from(options: &ImplOptions) -> Self {
+        MlaaOptions {
+            vertical_smoothing: options.vertical_smoothing,
+            horizontal_smoothing: options.horizontal_smoothing,
+            corner_smoothing: options.corner_smoothing,
+
+            strict_mode: options.strict_mode,
+            seam_split_position: options.seam_split_position,
+            seam_brightness_balance: options.seam_brigtness_balance,
+
+            blend_space: match options.blend_space {
+                mlaa_impl::BlendSpace::Srgb => MlaaBlendSpace::Srgb,
+                mlaa_impl::BlendSpace::Linear => MlaaBlendSpace::Linear,
+                mlaa_impl::BlendSpace::Oklab => MlaaBlendSpace::Oklab,
+                mlaa_impl::BlendSpace::Cielab => MlaaBlendSpace::Cielab,
+            } as u32,
+            brightness_metric: match options.brightness_metric {
+                mlaa_impl::BrightnessMetric::Rec601Luma => MlaaBrightnessMetric::Rec601Luma,
+                mlaa_impl::BrightnessMetric::Rec709Luma => MlaaBrightnessMetric::Rec709Luma,
+                mlaa_impl::BrightnessMetric::LinearLuminance => MlaaBrightnessMetric::LinearLuminance,
+                mlaa_impl::BrightnessMetric::CieLightness => MlaaBrightnessMetric::CieLightness,
+                mlaa_impl::BrightnessMetric::MaxChannel => MlaaBrightnessMetric::MaxChannel,
+                mlaa_impl::BrightnessMetric::AlphaWeightedLuma => MlaaBrightnessMetric::AlphaWeightedLuma,
+            } as u32,
+        }
+    }
+}
+
+impl TryFrom<&MlaaOptions> for ImplOptions {
+    type Error = MlaaStatus;
+
+    fn try_from(options: &MlaaOptions) -> Result<Self, Self::Error> {
+        Ok(ImplOptions {
+            vertical_smoothing: options.vertical_smoothing,
+            horizontal_smoothing: options.horizontal_smoothing,
+            corner_smoothing: options.corner_smoothing,
+
+            strict_mode: options.strict_mode,
+            seam_split_position: options.seam_split_position,
+            seam_brigtness_balance: options.seam_brightness_balance,
+
+            blend_space: match MlaaBlendSpace::try_from(options.blend_space)? {
+                MlaaBlendSpace::Srgb => mlaa_impl::BlendSpace::Srgb,
+                MlaaBlendSpace::Linear => mlaa_impl::BlendSpace::Linear,
+                MlaaBlendSpace::Oklab => mlaa_impl::BlendSpace::Oklab,
+                MlaaBlendSpace::Cielab => mlaa_impl::BlendSpace::Cielab,
+            },
+            brightness_metric: match MlaaBrightnessMetric::try_from(options.brightness_metric)? {
+                MlaaBrightnessMetric::Rec601Luma => mlaa_impl::BrightnessMetric::Rec601Luma,
+                MlaaBrightnessMetric::Rec709Luma => mlaa_impl::BrightnessMetric::Rec709Luma,
+                MlaaBrightnessMetric::LinearLuminance => mlaa_impl::BrightnessMetric::LinearLuminance,
+                MlaaBrightnessMetric::CieLightness => mlaa_impl::BrightnessMetric::CieLightness,
+                MlaaBrightnessMetric::MaxChannel => mlaa_impl::BrightnessMetric::MaxChannel,
+                MlaaBrightnessMetric::AlphaWeightedLuma => mlaa_impl::BrightnessMetric::AlphaWeightedLuma,
+            },
+        })
+    }
+}
+
+impl From<ImplFeature<[f32; 4]>> for MlaaFeature {
+    fn from(feature: ImplFeature<[f32; 4]>) -> Self {
+        let (kind, x, y, length, colors) = match feature {
+            ImplFeature::VerticalGradient { x, y, height, colors } => {
+                (MlaaFeatureKind::VerticalGradient, x, y, height, colors)
+            }
+            ImplFeature::HorizontalGradient { x, y, width, colors } => {
+                (MlaaFeatureKind::HorizontalGradient, x, y, width, colors)
+            }
+            ImplFeature::Corner { x, y, colors } => (MlaaFeatureKind::Corner, x as f32, y as f32, 0.0, colors),
+        };
+
+        MlaaFeature {
+            kind,
+            x,
+            y,
+            length,
+            colors: [colors.0, colors.1],
+        }
+    }
+}
+
+struct PixelLayout {
+    width: usize,
+    height: usize,
+    row_length: usize,
+    sample_count: usize,
+}
+
+impl PixelLayout {
+    fn new<S>(image: &MlaaImage) -> Result<PixelLayout, MlaaStatus> {
+        let sample_size = mem::size_of::<S>();
+        let (width, height) = (image.width as usize, image.height as usize);
+
+        if image.pixels.is_null() {
+            return Err(MlaaStatus::NullPointer);
+        }
+
+        if !(image.pixels as usize).is_multiple_of(mem::align_of::<S>())
+            || !image.stride.is_multiple_of(sample_size)
+            || (image.stride < width * 4 * sample_size)
+        {
+            return Err(MlaaStatus::InvalidImage);
+        }
+
+        let row_length = image.stride / sample_size;
+        let sample_count = if height == 0 {
+            0
+        } else {
+            row_length * (height - 1) + width * 4
+        };
+
+        Ok(PixelLayout {
+            width,
+            height,
+            row_length,
+            sample_count,
+        })
+    }
+
+    fn offset(&self, x: usize, y: usize) -> usize {
+        y * self.row_length + x * 4
+    }
+}
+
+// The samples of an `MlaaImage`, the pixel buffer is owned by the caller.
+struct ImageSamples<D> {
+    layout: PixelLayout,
+    samples: D,
+}
+
+impl<S, D> MlaaPixels for ImageSamples<D>
+where
+    S: Channel,
+    D: Deref<Target = [S]>,
+{
+    fn dimensions(&self) -> (usize, usize) {
+        (self.layout.width, self.layout.height)
+    }
+
+    fn get_rgba(&self, x: usize, y: usize) -> [f32; 4] {
+        let offset = self.layout.offset(x, y);
+        samples_to_rgba(&self.samples[offset..offset + 4])
+    }
+}
+
+impl<S, D> MlaaPixelsMut for ImageSamples<D>
+where
+    S: Channel,
+    D: DerefMut<Target = [S]>,
+{
+    fn put_rgba(&mut self, x: usize, y: usize, c: [f32; 4]) {
+        let offset = self.layout.offset(x, y);
+        rgba_to_samples(c, &mut self.samples[offset..offset + 4]);
+    }
+}
+
+unsafe fn image_samples<'a, S>(image: &MlaaImage) -> Result<ImageSamples<&'a [S]>, MlaaStatus> {
+    let layout = PixelLayout::new::<S>(image)?;
+    let samples = slice::from_raw_parts(image.pixels as *const S, layout.sample_count);
+    Ok(ImageSamples { layout, samples })
+}
+
+unsafe fn image_samples_mut<'a, S>(image: &MlaaImage) -> Result<ImageSamples<&'a mut [S]>, MlaaStatus> {
+    let layout = PixelLayout::new::<S>(image)?;
+    let samples = slice::from_raw_parts_mut(image.pixels as *mut S, layout.sample_count);
+    Ok(ImageSamples { layout, samples })
+}
+
+unsafe fn image_features(image: &MlaaImage, options: &ImplOptions) -> Result<Vec<ImplFeature<[f32; 4]>>, MlaaStatus> {
+    Ok(match MlaaPixelFormat::try_from(image.pixel_format)? {
+        MlaaPixelFormat::Rgba8 => mlaa_pixels_features(&image_samples::<u8>(image)?, options),
+        MlaaPixelFormat::Rgba16 => mlaa_pixels_features(&image_samples::<u16>(image)?, options),
+        MlaaPixelFormat::Rgba32F => mlaa_linear_pixels_features(&image_samples::<f32>(image)?, options),
+    })
+}
+
+// Converts the result into a status code, panics must not unwind into C code.
+fn guard(f: impl FnOnce() -> Result<(), MlaaStatus>) -> MlaaStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => MlaaStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => MlaaStatus::Panic,
+    }
+}
+
+// NULL options stand for the default options.
+unsafe fn checked_options(options: *const MlaaOptions) -> Result<ImplOptions, MlaaStatus> {
+    let options = options
+        .as_ref()
+        .map(ImplOptions::try_from)
+        .transpose()?
+        .unwrap_or_default();
+    options.validate().map_err(|_| MlaaStatus::InvalidOptions)?;
+    Ok(options)
+}
+
+#[no_mangle]
+pub extern "C" fn mlaa_status_message(status: u32) -> *const c_char {
+    let message: &'static [u8] = match MlaaStatus::try_from(status) {
+        Ok(MlaaStatus::Ok) => b"Success\0",
+        Ok(MlaaStatus::NullPointer) => b"A required pointer argument is NULL\0",
+        Ok(MlaaStatus::InvalidOptions) => b"The options are out of range\0",
+        Ok(MlaaStatus::InvalidImage) => b"The image stride or alignment is invalid\0",
+        Ok(MlaaStatus::UnknownPreset) => b"Unknown preset name\0",
+        Ok(MlaaStatus::Panic) => b"Internal error\0",
+        Err(_) => b"Unknown status\0",
+    };
+    message.as_ptr() as *const c_char
+}
+
+/// # Safety
+///
+/// `options` must be NULL or point to writable memory for an `MlaaOptions`.
+#[no_mangle]
+pub unsafe extern "C" fn mlaa_options_default(options: *mut MlaaOptions) -> MlaaStatus {
+    guard(|| {
+        let options = options.as_mut().ok_or(MlaaStatus::NullPointer)?;
+        *options = MlaaOptions::from(&ImplOptions::default());
+        Ok(())
+    })
+}
+
+/// # Safety
+///
+/// `name` must be NULL or a NUL-terminated string, `options` must be NULL or
+/// point to writable memory for an `MlaaOptions`.
+#[no_mangle]
+pub unsafe extern "C" fn mlaa_options_preset(name: *const c_char, options: *mut MlaaOptions) -> MlaaStatus {
+    guard(|| {
+        if name.is_null() {
+            return Err(MlaaStatus::NullPointer);
+        }
+        let options = options.as_mut().ok_or(MlaaStatus::NullPointer)?;
+
+        let name = CStr::from_ptr(name).to_str().map_err(|_| MlaaStatus::UnknownPreset)?;
+        *options = MlaaOptions::from(&ImplOptions::preset(name).ok_or(MlaaStatus::UnknownPreset)?);
+        Ok(())
+    })
+}
+
+/// # Safety
+///
+/// `image` must describe a valid pixel buffer, `options` must be NULL or point
+/// to an `MlaaOptions`. The callback must not unwind.
+#[no_mangle]
+pub unsafe extern "C" fn mlaa_features_callback(
+    image: *const MlaaImage,
+    options: *const MlaaOptions,
+    callback: MlaaFeatureCallback,
+    user_data: *mut c_void,
+) -> MlaaStatus {
+    guard(|| {
+        let image = image.as_ref().ok_or(MlaaStatus::NullPointer)?;
+        let options = checked_options(options)?;
+        let callback = callback.ok_or(MlaaStatus::NullPointer)?;
+
+        for feature in image_features(image, &options)? {
+            callback(&MlaaFeature::from(feature), user_data);
+        }
+
+        Ok(())
+    })
+}
+
+/// # Safety
+///
+/// `image` must describe a valid pixel buffer, `options` must be NULL or point
+/// to an `MlaaOptions`. The returned array has to be released with
+/// `mlaa_features_free`.
+#[no_mangle]
+pub unsafe extern "C" fn mlaa_features_array(
+    image: *const MlaaImage,
+    options: *const MlaaOptions,
+    features: *mut *mut MlaaFeature,
+    feature_count: *mut usize,
+) -> MlaaStatus {
+    guard(|| {
+        let image = image.as_ref().ok_or(MlaaStatus::NullPointer)?;
+        let options = checked_options(options)?;
+        let features = features.as_mut().ok_or(MlaaStatus::NullPointer)?;
+        let feature_count = feature_count.as_mut().ok_or(MlaaStatus::NullPointer)?;
+
+        let found_features = image_features(image, &options)?
+            .into_iter()
+            .map(MlaaFeature::from)
+            .collect::<Vec<_>>();
+
+        *feature_count = found_features.len();
+        *features = if found_features.is_empty() {
+            ptr::null_mut()
+        } else {
+            Box::into_raw(found_features.into_boxed_slice()) as *mut MlaaFeature
+        };
+
+        Ok(())
+    })
+}
+
+/// # Safety
+///
+/// `features` and `feature_count` must come from a single
+/// `mlaa_features_array` call, the array must not be freed twice.
+#[no_mangle]
+pub unsafe extern "C" fn mlaa_features_free(features: *mut MlaaFeature, feature_count: usize) {
+    if !features.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(features, feature_count)));
+    }
+}
+
+/// # Safety
+///
+/// `image` must describe a valid, writable pixel buffer, `options` must be
+/// NULL or point to an `MlaaOptions`.
+#[no_mangle]
+pub unsafe extern "C" fn mlaa_process_in_place(image: *const MlaaImage, options: *const MlaaOptions) -> MlaaStatus {
+    guard(|| {
+        let image = image.as_ref().ok_or(MlaaStatus::NullPointer)?;
+        let options = checked_options(options)?;
+
+        match MlaaPixelFormat::try_from(image.pixel_format)? {
+            MlaaPixelFormat::Rgba8 => mlaa_pixels_in_place(&mut image_samples_mut::<u8>(image)?, &options),
+            MlaaPixelFormat::Rgba16 => mlaa_pixels_in_place(&mut image_samples_mut::<u16>(image)?, &options),
+            MlaaPixelFormat::Rgba32F => mlaa_linear_pixels_in_place(&mut image_samples_mut::<f32>(image)?, &options),
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn staircase_rgba8() -> Vec<u8> {
+        (0..8 * 8)
+            .flat_map(|i| {
+                let v = if (i % 8) + (i / 8) < 8 { 0 } else { 255 };
+                [v, v, v, 255]
+            })
+            .collect()
+    }
+
+    fn default_options() -> MlaaOptions {
+        MlaaOptions::from(&ImplOptions::default())
+    }
+
+    #[test]
+    fn header_is_up_to_date() {
+        assert!(
+            include_str!(concat!(env!("OUT_DIR"), "/mlaa.h")) == include_str!("../include/mlaa.h"),
+            "include/mlaa.h is out of date, run `make header`"
+        );
+    }
+
+    #[test]
+    fn options_round_trip() {
+        for preset in ImplOptions::PRESET_NAMES {
+            let options = ImplOptions::preset(preset).unwrap();
+            assert!(ImplOptions::try_from(&MlaaOptions::from(&options)) == Ok(options));
+        }
+    }
+
+    #[test]
+    fn out_of_range_enums_are_rejected() {
+        let mut pixels = staircase_rgba8();
+        let mut image = MlaaImage {
+            pixels: pixels.as_mut_ptr() as *mut c_void,
+            width: 8,
+            height: 8,
+            stride: 8 * 4,
+            pixel_format: 3,
+        };
+
+        let status = unsafe { mlaa_process_in_place(&image, ptr::null()) };
+        assert_eq!(status, MlaaStatus::InvalidImage);
+
+        image.pixel_format = MlaaPixelFormat::Rgba8 as u32;
+        let options = MlaaOptions {
+            blend_space: 4,
+            ..default_options()
+        };
+        let status = unsafe { mlaa_process_in_place(&image, &options) };
+        assert_eq!(status, MlaaStatus::InvalidOptions);
+
+        let options = MlaaOptions {
+            brightness_metric: u32::MAX,
+            ..default_options()
+        };
+        let status = unsafe { mlaa_process_in_place(&image, &options) };
+        assert_eq!(status, MlaaStatus::InvalidOptions);
+
+        assert_eq!(pixels, staircase_rgba8());
+    }
+
+    #[test]
+    fn unknown_status_has_a_message() {
+        let message = unsafe { CStr::from_ptr(mlaa_status_message(u32::MAX)) };
+        assert_eq!(message.to_str(), Ok("Unknown status"));
+
+        let message = unsafe { CStr::from_ptr(mlaa_status_message(MlaaStatus::Ok as u32)) };
+        assert_eq!(message.to_str(), Ok("Success"));
+    }
+
+    #[test]
+    fn staircase_gets_blended() {
+        let mut pixels = staircase_rgba8();
+        let image = MlaaImage {
+            pixels: pixels.as_mut_ptr() as *mut c_void,
+            width: 8,
+            height: 8,
+            stride: 8 * 4,
+            pixel_format: MlaaPixelFormat::Rgba8 as u32,
+        };
+
+        let status = unsafe { mlaa_process_in_place(&image, ptr::null()) };
+        assert_eq!(status, MlaaStatus::Ok);
+        assert!(pixels.iter().any(|&v| (v != 0) && (v != 255)));
+    }
+
+    #[test]
+    fn float_pixels_are_linear() {
+        let staircase = image::Rgba32FImage::from_fn(8, 8, |x, y| {
+            let v = if x + y < 8 { 0.0 } else { 4.0 };
+            image::Rgba([v, v, v, 1.0])
+        });
+
+        let mut pixels = staircase.as_raw().clone();
+        let image = MlaaImage {
+            pixels: pixels.as_mut_ptr() as *mut c_void,
+            width: 8,
+            height: 8,
+            stride: 8 * 4 * mem::size_of::<f32>(),
+            pixel_format: MlaaPixelFormat::Rgba32F as u32,
+        };
+
+        let status = unsafe { mlaa_process_in_place(&image, ptr::null()) };
+        assert_eq!(status, MlaaStatus::Ok);
+        assert!(pixels == *mlaa_impl::mlaa_linear_image_buffer(&staircase, &ImplOptions::default()).as_raw());
+    }
+}
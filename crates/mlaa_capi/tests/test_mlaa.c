@@ -0,0 +1,235 @@
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "mlaa.h"
+
+#define WIDTH 8
+#define HEIGHT 8
+#define PADDING 3
+
+static int failures = 0;
+
+#define CHECK(condition)                                                    \
+    do {                                                                    \
+        if (!(condition)) {                                                 \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #condition);                                            \
+            failures++;                                                     \
+        }                                                                   \
+    } while (0)
+
+#define CHECK_STATUS(expression, expected)                                  \
+    do {                                                                    \
+        MlaaStatus status = (expression);                                   \
+        if (status != (expected)) {                                         \
+            fprintf(stderr, "%s:%d: %s returned %d (%s)\n", __FILE__,       \
+                    __LINE__, #expression, status,                          \
+                    mlaa_status_message(status));                           \
+            failures++;                                                     \
+        }                                                                   \
+    } while (0)
+
+// A black staircase on white, produces features of every kind.
+static int is_dark(size_t x, size_t y) {
+    return (x + y) < WIDTH;
+}
+
+static void fill_rgba8(uint8_t *pixels, size_t stride) {
+    for (size_t y = 0; y < HEIGHT; y++) {
+        uint8_t *row = pixels + y * stride;
+        for (size_t x = 0; x < WIDTH; x++) {
+            uint8_t value = is_dark(x, y) ? 0 : 255;
+            row[x * 4 + 0] = value;
+            row[x * 4 + 1] = value;
+            row[x * 4 + 2] = value;
+            row[x * 4 + 3] = 255;
+        }
+    }
+}
+
+static void fill_rgba16(uint16_t *pixels, size_t stride) {
+    for (size_t y = 0; y < HEIGHT; y++) {
+        uint16_t *row = (uint16_t *)((uint8_t *)pixels + y * stride);
+        for (size_t x = 0; x < WIDTH; x++) {
+            uint16_t value = is_dark(x, y) ? 0 : 65535;
+            row[x * 4 + 0] = value;
+            row[x * 4 + 1] = value;
+            row[x * 4 + 2] = value;
+            row[x * 4 + 3] = 65535;
+        }
+    }
+}
+
+static void fill_rgba32f(float *pixels, size_t stride) {
+    for (size_t y = 0; y < HEIGHT; y++) {
+        float *row = (float *)((uint8_t *)pixels + y * stride);
+        for (size_t x = 0; x < WIDTH; x++) {
+            float value = is_dark(x, y) ? 0.0f : 1.0f;
+            row[x * 4 + 0] = value;
+            row[x * 4 + 1] = value;
+            row[x * 4 + 2] = value;
+            row[x * 4 + 3] = 1.0f;
+        }
+    }
+}
+
+static void count_feature(const MlaaFeature *feature, void *user_data) {
+    CHECK(feature->kind <= MLAA_FEATURE_KIND_CORNER);
+    (*(size_t *)user_data)++;
+}
+
+static void test_options(void) {
+    MlaaOptions options;
+
+    CHECK_STATUS(mlaa_options_default(&options), MLAA_STATUS_OK);
+    CHECK(options.vertical_smoothing);
+    CHECK(options.horizontal_smoothing);
+    CHECK(options.seam_split_position >= 0.0f && options.seam_split_position <= 1.0f);
+
+    CHECK_STATUS(mlaa_options_preset("pixel-art", &options), MLAA_STATUS_OK);
+    CHECK_STATUS(mlaa_options_preset("no-such-preset", &options), MLAA_STATUS_UNKNOWN_PRESET);
+    CHECK_STATUS(mlaa_options_preset(NULL, &options), MLAA_STATUS_NULL_POINTER);
+    CHECK_STATUS(mlaa_options_default(NULL), MLAA_STATUS_NULL_POINTER);
+
+    CHECK(strlen(mlaa_status_message(MLAA_STATUS_INVALID_IMAGE)) > 0);
+    CHECK(strcmp(mlaa_status_message(1000), "Unknown status") == 0);
+}
+
+static void test_features(void) {
+    size_t stride = (WIDTH + PADDING) * 4;
+    uint8_t *pixels = calloc(HEIGHT, stride);
+    fill_rgba8(pixels, stride);
+
+    MlaaImage image = { pixels, WIDTH, HEIGHT, stride, MLAA_PIXEL_FORMAT_RGBA8 };
+
+    size_t callback_count = 0;
+    CHECK_STATUS(mlaa_features_callback(&image, NULL, count_feature, &callback_count), MLAA_STATUS_OK);
+    CHECK(callback_count > 0);
+
+    MlaaFeature *features = NULL;
+    size_t feature_count = 0;
+    CHECK_STATUS(mlaa_features_array(&image, NULL, &features, &feature_count), MLAA_STATUS_OK);
+    CHECK(feature_count == callback_count);
+    CHECK(features != NULL);
+    mlaa_features_free(features, feature_count);
+
+    CHECK_STATUS(mlaa_features_callback(&image, NULL, NULL, NULL), MLAA_STATUS_NULL_POINTER);
+    CHECK_STATUS(mlaa_features_array(NULL, NULL, &features, &feature_count), MLAA_STATUS_NULL_POINTER);
+
+    MlaaOptions options;
+    mlaa_options_default(&options);
+    options.seam_split_position = 2.0f;
+    CHECK_STATUS(mlaa_features_array(&image, &options, &features, &feature_count), MLAA_STATUS_INVALID_OPTIONS);
+
+    // Out of range enum values are rejected instead of being used.
+    mlaa_options_default(&options);
+    options.blend_space = 1000;
+    CHECK_STATUS(mlaa_features_array(&image, &options, &features, &feature_count), MLAA_STATUS_INVALID_OPTIONS);
+
+    mlaa_options_default(&options);
+    options.brightness_metric = 1000;
+    CHECK_STATUS(mlaa_features_array(&image, &options, &features, &feature_count), MLAA_STATUS_INVALID_OPTIONS);
+
+    image.pixel_format = 1000;
+    CHECK_STATUS(mlaa_features_array(&image, NULL, &features, &feature_count), MLAA_STATUS_INVALID_IMAGE);
+
+    free(pixels);
+}
+
+static void test_process_rgba8(void) {
+    size_t stride = (WIDTH + PADDING) * 4;
+    uint8_t *pixels = calloc(HEIGHT, stride);
+    fill_rgba8(pixels, stride);
+
+    MlaaImage image = { pixels, WIDTH, HEIGHT, stride, MLAA_PIXEL_FORMAT_RGBA8 };
+    CHECK_STATUS(mlaa_process_in_place(&image, NULL), MLAA_STATUS_OK);
+
+    // Some pixels along the staircase get blended, the row padding is left
+    // untouched.
+    int blended = 0;
+    for (size_t y = 0; y < HEIGHT; y++) {
+        for (size_t x = 0; x < WIDTH; x++) {
+            uint8_t value = pixels[y * stride + x * 4];
+            blended |= (value != 0) && (value != 255);
+        }
+        for (size_t i = WIDTH * 4; i < stride; i++) {
+            CHECK(pixels[y * stride + i] == 0);
+        }
+    }
+    CHECK(blended);
+
+    // Stride shorter than a row.
+    image.stride = WIDTH * 4 - 1;
+    CHECK_STATUS(mlaa_process_in_place(&image, NULL), MLAA_STATUS_INVALID_IMAGE);
+
+    image.stride = stride;
+    image.pixels = NULL;
+    CHECK_STATUS(mlaa_process_in_place(&image, NULL), MLAA_STATUS_NULL_POINTER);
+
+    free(pixels);
+}
+
+static void test_process_rgba16(void) {
+    size_t stride = (WIDTH + PADDING) * 8;
+    uint16_t *pixels = calloc(HEIGHT, stride);
+    fill_rgba16(pixels, stride);
+
+    MlaaImage image = { pixels, WIDTH, HEIGHT, stride, MLAA_PIXEL_FORMAT_RGBA16 };
+    CHECK_STATUS(mlaa_process_in_place(&image, NULL), MLAA_STATUS_OK);
+
+    int blended = 0;
+    for (size_t y = 0; y < HEIGHT; y++) {
+        uint16_t *row = (uint16_t *)((uint8_t *)pixels + y * stride);
+        for (size_t x = 0; x < WIDTH; x++) {
+            blended |= (row[x * 4] != 0) && (row[x * 4] != 65535);
+        }
+    }
+    CHECK(blended);
+
+    // Stride not a multiple of the sample size.
+    image.stride = stride + 1;
+    CHECK_STATUS(mlaa_process_in_place(&image, NULL), MLAA_STATUS_INVALID_IMAGE);
+
+    free(pixels);
+}
+
+static void test_process_rgba32f(void) {
+    size_t stride = (WIDTH + PADDING) * 16;
+    float *pixels = calloc(HEIGHT, stride);
+    fill_rgba32f(pixels, stride);
+
+    MlaaImage image = { pixels, WIDTH, HEIGHT, stride, MLAA_PIXEL_FORMAT_RGBA32_F };
+
+    MlaaOptions options;
+    CHECK_STATUS(mlaa_options_preset("soft", &options), MLAA_STATUS_OK);
+    CHECK_STATUS(mlaa_process_in_place(&image, &options), MLAA_STATUS_OK);
+
+    int blended = 0;
+    for (size_t y = 0; y < HEIGHT; y++) {
+        float *row = (float *)((uint8_t *)pixels + y * stride);
+        for (size_t x = 0; x < WIDTH; x++) {
+            blended |= (row[x * 4] > 0.0f) && (row[x * 4] < 1.0f);
+        }
+    }
+    CHECK(blended);
+
+    free(pixels);
+}
+
+int main(void) {
+    test_options();
+    test_features();
+    test_process_rgba8();
+    test_process_rgba16();
+    test_process_rgba32f();
+
+    if (failures > 0) {
+        fprintf(stderr, "%d check(s) failed\n", failures);
+        return EXIT_FAILURE;
+    }
+
+    printf("All checks passed\n");
+    return EXIT_SUCCESS;
+}
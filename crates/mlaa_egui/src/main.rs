@@ -1,8 +1,8 @@
-use eframe::egui::{self, DragValue, PointerButton, Sense};
-use eframe::emath::{lerp, remap};
-use eframe::epaint::{vec2, Color32, Rect, Rgba, Stroke};
+use eframe::egui::{self, ComboBox, DragValue, PointerButton, Sense};
+use eframe::emath::remap;
+use eframe::epaint::{vec2, Color32, Rect, Stroke};
 
-use mlaa_impl::{mlaa_features, mlaa_painter, MlaaFeature, MlaaOptions};
+use mlaa_impl::{mlaa_features, mlaa_painter, BlendSpace, BrightnessMetric, MlaaFeature, MlaaOptions};
 
 const IMAGE_WIDTH: usize = 32;
 const IMAGE_HEIGHT: usize = 24;
@@ -39,6 +39,15 @@ impl Default for MlaaApplication {
     }
 }
 
+fn color32_to_rgba(c: Color32) -> [f32; 4] {
+    c.to_srgba_unmultiplied().map(|v| v as f32 / 255.0)
+}
+
+fn rgba_to_color32(c: [f32; 4]) -> Color32 {
+    let [r, g, b, a] = c.map(|v| (v * 255.0).round() as u8);
+    Color32::from_rgba_unmultiplied(r, g, b, a)
+}
+
 impl MlaaApplication {
     fn generate_test_image(&mut self) {
         let aspect_ratio = if IMAGE_WIDTH > IMAGE_HEIGHT {
@@ -80,7 +89,7 @@ impl MlaaApplication {
 
                 self.image_pixels[y as usize][x as usize]
             },
-            |c| Rgba::from(c).intensity(),
+            |c| self.mlaa_options.brightness_metric.brightness(color32_to_rgba(c)),
             &self.mlaa_options,
             |mlaa_feature| self.mlaa_features.push(mlaa_feature),
         );
@@ -111,6 +120,29 @@ impl eframe::App for MlaaApplication {
                 });
                 ui.separator();
 
+                ui.vertical(|ui| {
+                    ui.label("Preset");
+
+                    let current_preset_name = MlaaOptions::PRESET_NAMES
+                        .into_iter()
+                        .find(|&preset_name| MlaaOptions::preset(preset_name).as_ref() == Some(&self.mlaa_options));
+
+                    ComboBox::from_id_source("preset")
+                        .selected_text(current_preset_name.unwrap_or("Custom"))
+                        .show_ui(ui, |ui| {
+                            for preset_name in MlaaOptions::PRESET_NAMES {
+                                if ui
+                                    .selectable_label(current_preset_name == Some(preset_name), preset_name)
+                                    .clicked()
+                                {
+                                    self.mlaa_options = MlaaOptions::preset(preset_name).unwrap();
+                                    needs_feature_recalc = true;
+                                }
+                            }
+                        });
+                });
+                ui.separator();
+
                 ui.vertical(|ui| {
                     ui.label("Seam split position");
 
@@ -130,6 +162,41 @@ impl eframe::App for MlaaApplication {
                 });
                 ui.separator();
 
+                ui.vertical(|ui| {
+                    ui.label("Blend space");
+
+                    ComboBox::from_id_source("blend_space")
+                        .selected_text(self.mlaa_options.blend_space.label())
+                        .show_ui(ui, |ui| {
+                            for blend_space in BlendSpace::ALL {
+                                ui.selectable_value(
+                                    &mut self.mlaa_options.blend_space,
+                                    blend_space,
+                                    blend_space.label(),
+                                );
+                            }
+                        });
+
+                    ui.label("Brightness metric");
+
+                    let previous_brightness_metric = self.mlaa_options.brightness_metric;
+                    ComboBox::from_id_source("brightness_metric")
+                        .selected_text(self.mlaa_options.brightness_metric.label())
+                        .show_ui(ui, |ui| {
+                            for brightness_metric in BrightnessMetric::ALL {
+                                ui.selectable_value(
+                                    &mut self.mlaa_options.brightness_metric,
+                                    brightness_metric,
+                                    brightness_metric.label(),
+                                );
+                            }
+                        });
+                    if self.mlaa_options.brightness_metric != previous_brightness_metric {
+                        needs_feature_recalc = true;
+                    }
+                });
+                ui.separator();
+
                 ui.vertical(|ui| {
                     ui.label("Smoothing");
                     if (ui.checkbox(&mut self.mlaa_options.vertical_smoothing, "Vertical")
@@ -191,7 +258,13 @@ impl eframe::App for MlaaApplication {
                 // Draw features
                 for mlaa_feature in &self.mlaa_features {
                     mlaa_painter(
-                        |color_a, color_b, t| lerp(Rgba::from(color_a)..=Rgba::from(color_b), t).into(),
+                        |color_a, color_b, t| {
+                            rgba_to_color32(self.mlaa_options.blend_space.blend(
+                                color32_to_rgba(color_a),
+                                color32_to_rgba(color_b),
+                                t,
+                            ))
+                        },
                         |x, y, color| {
                             let pixel_rect =
                                 Rect::from_min_size(rect.left_top() + cell_size * vec2(x as f32, y as f32), cell_size);
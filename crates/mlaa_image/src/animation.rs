@@ -0,0 +1,709 @@
+use std::error::Error;
+use std::io::Cursor;
+
+use image::{ImageBuffer, ImageFormat, Pixel, Primitive, Rgba, RgbaImage};
+use png::{BitDepth, BlendOp, DisposeOp, Transformations};
+
+use mlaa_impl::{mlaa_image_buffer, rgba_to_samples, samples_to_rgba, Channel, MlaaOptions};
+
+use crate::indexed::{closest_palette_color, QuantizationError};
+use crate::metadata::ImageMetadata;
+
+// Every frame is drawn onto the canvas first and the composited canvas is
+// antialiased, so transparent frame pixels and frame borders don't produce
+// edges. Only the pixels of the frame rectangle are written back into the
+// frame, the frame rectangles, disposal and blending, frame timing and the
+// loop count are copied unchanged from the input.
+
+pub fn is_animated_png(png_data: &[u8]) -> Result<bool, Box<dyn Error>> {
+    let reader = png::Decoder::new(Cursor::new(png_data)).read_info()?;
+    Ok(reader.info().animation_control.is_some())
+}
+
+pub fn is_animated_gif(gif_data: &[u8]) -> Result<bool, Box<dyn Error>> {
+    let mut options = gif::DecodeOptions::new();
+    options.skip_frame_decoding(true);
+
+    let mut decoder = options.read_info(Cursor::new(gif_data))?;
+    let mut frame_count = 0;
+    while decoder.next_frame_info()?.is_some() {
+        frame_count += 1;
+    }
+
+    Ok(frame_count > 1)
+}
+
+pub fn is_animated(image_data: &[u8], image_format: ImageFormat) -> Result<bool, Box<dyn Error>> {
+    match image_format {
+        ImageFormat::Gif => is_animated_gif(image_data),
+        ImageFormat::Png => is_animated_png(image_data),
+        _ => Ok(false),
+    }
+}
+
+trait PngSample: Channel {
+    const SIZE: usize;
+
+    fn read_be(bytes: &[u8]) -> Self;
+    fn write_be(self, bytes: &mut [u8]);
+}
+
+impl PngSample for u8 {
+    const SIZE: usize = 1;
+
+    fn read_be(bytes: &[u8]) -> Self {
+        bytes[0]
+    }
+
+    fn write_be(self, bytes: &mut [u8]) {
+        bytes[0] = self;
+    }
+}
+
+impl PngSample for u16 {
+    const SIZE: usize = 2;
+
+    fn read_be(bytes: &[u8]) -> Self {
+        u16::from_be_bytes([bytes[0], bytes[1]])
+    }
+
+    fn write_be(self, bytes: &mut [u8]) {
+        bytes.copy_from_slice(&self.to_be_bytes());
+    }
+}
+
+type RgbaBuffer<T> = ImageBuffer<Rgba<T>, Vec<T>>;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Disposal {
+    Keep,
+    Background,
+    Previous,
+}
+
+impl From<DisposeOp> for Disposal {
+    fn from(dispose_op: DisposeOp) -> Self {
+        match dispose_op {
+            DisposeOp::None => Disposal::Keep,
+            DisposeOp::Background => Disposal::Background,
+            DisposeOp::Previous => Disposal::Previous,
+        }
+    }
+}
+
+impl From<gif::DisposalMethod> for Disposal {
+    fn from(dispose: gif::DisposalMethod) -> Self {
+        match dispose {
+            gif::DisposalMethod::Any | gif::DisposalMethod::Keep => Disposal::Keep,
+            gif::DisposalMethod::Background => Disposal::Background,
+            gif::DisposalMethod::Previous => Disposal::Previous,
+        }
+    }
+}
+
+// Non-premultiplied source over compositing.
+fn blend_over<T: Channel>(source: Rgba<T>, destination: Rgba<T>) -> Rgba<T> {
+    let (s, d) = (samples_to_rgba(&source.0), samples_to_rgba(&destination.0));
+    let alpha = s[3] + d[3] * (1.0 - s[3]);
+
+    if s[3] >= 1.0 {
+        source
+    } else if s[3] <= 0.0 {
+        destination
+    } else {
+        let blend = |channel: usize| (s[channel] * s[3] + d[channel] * d[3] * (1.0 - s[3])) / alpha;
+
+        let mut output = Rgba([T::DEFAULT_MIN_VALUE; 4]);
+        rgba_to_samples([blend(0), blend(1), blend(2), alpha], &mut output.0);
+        output
+    }
+}
+
+// The canvas starts out fully transparent, the disposal of a frame is applied
+// right before drawing the next one.
+struct Canvas<T>
+where
+    T: Primitive,
+    Rgba<T>: Pixel<Subpixel = T>,
+{
+    image: RgbaBuffer<T>,
+    previous_image: Option<RgbaBuffer<T>>,
+    frame_rect: (u32, u32, u32, u32),
+    disposal: Disposal,
+}
+
+impl<T> Canvas<T>
+where
+    T: Channel,
+    Rgba<T>: Pixel<Subpixel = T>,
+{
+    fn new(width: u32, height: u32) -> Canvas<T> {
+        Canvas {
+            image: ImageBuffer::new(width, height),
+            previous_image: None,
+            frame_rect: (0, 0, 0, 0),
+            disposal: Disposal::Keep,
+        }
+    }
+
+    fn draw_frame(
+        &mut self,
+        frame: &RgbaBuffer<T>,
+        (frame_x, frame_y): (u32, u32),
+        blend: bool,
+        disposal: Disposal,
+    ) -> Result<(), Box<dyn Error>> {
+        match self.disposal {
+            Disposal::Keep => {}
+            Disposal::Background => {
+                let (x, y, width, height) = self.frame_rect;
+                for (px, py) in (y..y + height).flat_map(|py| (x..x + width).map(move |px| (px, py))) {
+                    self.image.put_pixel(px, py, Rgba([T::DEFAULT_MIN_VALUE; 4]));
+                }
+            }
+            Disposal::Previous => {
+                if let Some(previous_image) = self.previous_image.take() {
+                    self.image = previous_image;
+                }
+            }
+        }
+
+        if (frame_x as u64 + frame.width() as u64 > self.image.width() as u64)
+            || (frame_y as u64 + frame.height() as u64 > self.image.height() as u64)
+        {
+            return Err("Animation frame is outside of the canvas".into());
+        }
+
+        // Restoring the state before the first frame clears the canvas.
+        self.previous_image = (disposal == Disposal::Previous).then(|| self.image.clone());
+        self.frame_rect = (frame_x, frame_y, frame.width(), frame.height());
+        self.disposal = disposal;
+
+        for (x, y, &pixel) in frame.enumerate_pixels() {
+            let canvas_pixel = self.image.get_pixel_mut(frame_x + x, frame_y + y);
+            *canvas_pixel = if blend { blend_over(pixel, *canvas_pixel) } else { pixel };
+        }
+
+        Ok(())
+    }
+
+    // Pixels of the last frame's rectangle changed by antialiasing the
+    // canvas, in frame coordinates. Every other frame pixel keeps its input
+    // value, so transparent pixels of blended frames stay transparent.
+    fn antialiased_pixels(&self, mlaa_options: &MlaaOptions) -> Vec<(u32, u32, Rgba<T>)> {
+        let antialiased_image = mlaa_image_buffer(&self.image, mlaa_options);
+        let (frame_x, frame_y, width, height) = self.frame_rect;
+
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter_map(|(x, y)| {
+                let pixel = *antialiased_image.get_pixel(frame_x + x, frame_y + y);
+                (pixel != *self.image.get_pixel(frame_x + x, frame_y + y)).then_some((x, y, pixel))
+            })
+            .collect()
+    }
+}
+
+fn png_frame_to_rgba<T: PngSample>(
+    frame_data: &[u8],
+    width: u32,
+    height: u32,
+    channel_count: usize,
+) -> Result<RgbaBuffer<T>, Box<dyn Error>>
+where
+    Rgba<T>: Pixel<Subpixel = T>,
+{
+    let samples = frame_data.chunks_exact(T::SIZE).map(T::read_be).collect::<Vec<T>>();
+
+    let rgba_samples = samples
+        .chunks_exact(channel_count)
+        .flat_map(|pixel| {
+            let mut rgba = [T::DEFAULT_MIN_VALUE; 4];
+            rgba_to_samples(samples_to_rgba(pixel), &mut rgba);
+            rgba
+        })
+        .collect::<Vec<T>>();
+
+    ImageBuffer::from_vec(width, height, rgba_samples).ok_or_else(|| "Invalid APNG frame size".into())
+}
+
+fn png_frame_data<T: PngSample>(image: &RgbaBuffer<T>) -> Vec<u8>
+where
+    Rgba<T>: Pixel<Subpixel = T>,
+{
+    let mut frame_data = vec![0; image.len() * T::SIZE];
+    for (sample, bytes) in image.iter().zip(frame_data.chunks_exact_mut(T::SIZE)) {
+        sample.write_be(bytes);
+    }
+    frame_data
+}
+
+// The output is always RGBA, disposing frames can make any animation
+// translucent.
+fn process_apng_frames<T>(
+    mut reader: png::Reader<Cursor<&[u8]>>,
+    mlaa_options: &MlaaOptions,
+    metadata: &ImageMetadata,
+) -> Result<Vec<u8>, Box<dyn Error>>
+where
+    T: PngSample,
+    Rgba<T>: Pixel<Subpixel = T>,
+{
+    let (width, height) = reader.info().size();
+    let animation_control = reader.info().animation_control.ok_or("PNG file is not animated")?;
+    let (color_type, bit_depth) = reader.output_color_type();
+
+    // The default image isn't part of the animation when it has no frame
+    // control chunk.
+    let separate_default_image = reader.info().frame_control.is_none();
+    let frame_count = animation_control.num_frames as usize + usize::from(separate_default_image);
+
+    let mut canvas = Canvas::<T>::new(width, height);
+    let mut png_output = Vec::new();
+
+    {
+        let mut encoder = metadata.png_encoder(&mut png_output, width, height)?;
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(bit_depth);
+        encoder.set_animated(animation_control.num_frames, animation_control.num_plays)?;
+        encoder.set_sep_def_img(separate_default_image)?;
+
+        let mut writer = encoder.write_header()?;
+        let mut frame_data = vec![0; reader.output_buffer_size()];
+
+        for _ in 0..frame_count {
+            let frame_info = reader.next_frame(&mut frame_data)?;
+            let frame = png_frame_to_rgba::<T>(
+                &frame_data[..frame_info.buffer_size()],
+                frame_info.width,
+                frame_info.height,
+                color_type.samples(),
+            )?;
+
+            if let Some(frame_control) = reader.info().frame_control {
+                canvas.draw_frame(
+                    &frame,
+                    (frame_control.x_offset, frame_control.y_offset),
+                    frame_control.blend_op == BlendOp::Over,
+                    frame_control.dispose_op.into(),
+                )?;
+
+                // The antialiased pixels are taken from the opaque parts of
+                // the canvas in most animations, blending them over the
+                // previous canvas leaves them unchanged.
+                let mut output_frame = frame;
+                for (x, y, pixel) in canvas.antialiased_pixels(mlaa_options) {
+                    output_frame.put_pixel(x, y, pixel);
+                }
+
+                writer.reset_frame_position()?;
+                writer.set_frame_dimension(frame_control.width, frame_control.height)?;
+                writer.set_frame_position(frame_control.x_offset, frame_control.y_offset)?;
+                writer.set_frame_delay(frame_control.delay_num, frame_control.delay_den)?;
+                writer.set_dispose_op(frame_control.dispose_op)?;
+                writer.set_blend_op(frame_control.blend_op)?;
+                writer.write_image_data(&png_frame_data(&output_frame))?;
+            } else {
+                writer.write_image_data(&png_frame_data(&mlaa_image_buffer(&frame, mlaa_options)))?;
+            }
+        }
+
+        writer.finish()?;
+    }
+
+    Ok(png_output)
+}
+
+pub fn process_apng(
+    png_data: &[u8],
+    mlaa_options: &MlaaOptions,
+    metadata: &ImageMetadata,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut decoder = png::Decoder::new(Cursor::new(png_data));
+    // Indexed and low bit depth frames are expanded to 8 bits per sample.
+    decoder.set_transformations(Transformations::EXPAND);
+
+    let reader = decoder.read_info()?;
+
+    match reader.output_color_type() {
+        (_, BitDepth::Eight) => process_apng_frames::<u8>(reader, mlaa_options, metadata),
+        (_, BitDepth::Sixteen) => process_apng_frames::<u16>(reader, mlaa_options, metadata),
+        (color_type, bit_depth) => Err(format!("Unsupported APNG color type {:?} {:?}", color_type, bit_depth).into()),
+    }
+}
+
+pub fn process_gif(
+    gif_data: &[u8],
+    mlaa_options: &MlaaOptions,
+) -> Result<(Vec<u8>, QuantizationError), Box<dyn Error>> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+
+    let mut decoder = options.read_info(Cursor::new(gif_data))?;
+    let (width, height) = (decoder.width(), decoder.height());
+    let global_palette = decoder.global_palette().map(<[u8]>::to_vec);
+
+    let mut frames = Vec::new();
+    while let Some(frame) = decoder.read_next_frame()? {
+        frames.push(frame.clone());
+    }
+
+    // The loop count is only known after reading the frames, the extension
+    // carrying it may come after the first frame.
+    let repeat = decoder.repeat();
+
+    let mut canvas = Canvas::<u8>::new(width as u32, height as u32);
+    let mut quantization_error = QuantizationError::default();
+    let mut gif_output = Vec::new();
+
+    {
+        let mut encoder = gif::Encoder::new(&mut gif_output, width, height, global_palette.as_deref().unwrap_or(&[]))?;
+        // Without a loop extension the animation is played once, writing one
+        // with a zero count would make it loop forever.
+        if repeat != gif::Repeat::Finite(0) {
+            encoder.set_repeat(repeat)?;
+        }
+
+        for frame in &frames {
+            let palette_rgb = frame
+                .palette
+                .as_deref()
+                .or(global_palette.as_deref())
+                .ok_or("GIF frame has no palette")?;
+
+            let palette = palette_rgb
+                .chunks_exact(3)
+                .enumerate()
+                .map(|(index, rgb)| {
+                    let alpha = if frame.transparent == Some(index as u8) { 0 } else { 255 };
+                    [rgb[0], rgb[1], rgb[2], alpha]
+                })
+                .collect::<Vec<_>>();
+
+            // The decoder already deinterlaced the frame.
+            let frame_image = RgbaImage::from_fn(frame.width as u32, frame.height as u32, |x, y| {
+                let index = frame.buffer[(y * frame.width as u32 + x) as usize];
+                Rgba(palette.get(index as usize).copied().unwrap_or([0; 4]))
+            });
+            canvas.draw_frame(
+                &frame_image,
+                (frame.left as u32, frame.top as u32),
+                true,
+                frame.dispose.into(),
+            )?;
+
+            // Antialiased pixels are quantized against the palette of the
+            // frame, the frames keep their palettes.
+            let mut buffer = frame.buffer.to_vec();
+            for (x, y, pixel) in canvas.antialiased_pixels(mlaa_options) {
+                if let Some((palette_index, error)) = closest_palette_color(&palette, samples_to_rgba(&pixel.0)) {
+                    buffer[(y * frame.width as u32 + x) as usize] = palette_index;
+                    quantization_error.add(error);
+                }
+            }
+
+            let output_frame = gif::Frame {
+                interlaced: false,
+                buffer: buffer.into(),
+                ..frame.clone()
+            };
+            encoder.write_frame(&output_frame)?;
+        }
+    }
+
+    Ok((gif_output, quantization_error))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::codecs::gif::GifDecoder;
+    use image::codecs::png::PngDecoder;
+    use image::{AnimationDecoder, Frame};
+
+    use super::*;
+
+    // A black staircase on white, the bottom right quarter is white.
+    fn staircase_rgba8() -> RgbaImage {
+        RgbaImage::from_fn(8, 8, |x, y| {
+            if x + y < 8 {
+                Rgba([0, 0, 0, 255])
+            } else {
+                Rgba([255; 4])
+            }
+        })
+    }
+
+    // White on its right half and transparent on its left half, drawing it
+    // over the bottom right quarter of the staircase doesn't change the canvas.
+    fn half_transparent_rgba8() -> RgbaImage {
+        RgbaImage::from_fn(4, 4, |x, _| if x < 2 { Rgba([0; 4]) } else { Rgba([255; 4]) })
+    }
+
+    fn encode_apng(frames: &[(RgbaImage, (u32, u32), DisposeOp, BlendOp)]) -> Vec<u8> {
+        let mut png_data = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut png_data, 8, 8);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_animated(frames.len() as u32, 0).unwrap();
+
+            let mut writer = encoder.write_header().unwrap();
+            for (frame, (x, y), dispose_op, blend_op) in frames {
+                writer.reset_frame_position().unwrap();
+                writer.set_frame_dimension(frame.width(), frame.height()).unwrap();
+                writer.set_frame_position(*x, *y).unwrap();
+                writer.set_dispose_op(*dispose_op).unwrap();
+                writer.set_blend_op(*blend_op).unwrap();
+                writer.write_image_data(frame.as_raw()).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        png_data
+    }
+
+    // Rectangle, disposal and blending of every frame.
+    fn apng_frame_controls(png_data: &[u8]) -> Vec<(u32, u32, u32, u32, DisposeOp, BlendOp)> {
+        let mut reader = png::Decoder::new(Cursor::new(png_data)).read_info().unwrap();
+        let frame_count = reader.info().animation_control.unwrap().num_frames;
+        let mut frame_data = vec![0; reader.output_buffer_size()];
+
+        (0..frame_count)
+            .map(|_| {
+                reader.next_frame(&mut frame_data).unwrap();
+                let frame_control = reader.info().frame_control.unwrap();
+                (
+                    frame_control.x_offset,
+                    frame_control.y_offset,
+                    frame_control.width,
+                    frame_control.height,
+                    frame_control.dispose_op,
+                    frame_control.blend_op,
+                )
+            })
+            .collect()
+    }
+
+    // Rectangle, disposal, transparency and palette of every frame.
+    type GifFrameFields = (u16, u16, u16, u16, gif::DisposalMethod, Option<u8>, Option<Vec<u8>>);
+
+    fn gif_frame_fields(gif_data: &[u8]) -> Vec<GifFrameFields> {
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(Cursor::new(gif_data)).unwrap();
+
+        let mut frame_fields = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frame_fields.push((
+                frame.left,
+                frame.top,
+                frame.width,
+                frame.height,
+                frame.dispose,
+                frame.transparent,
+                frame.palette.clone(),
+            ));
+        }
+        frame_fields
+    }
+
+    fn apng_frames(png_data: &[u8]) -> Vec<Frame> {
+        let decoder = PngDecoder::new(Cursor::new(png_data)).unwrap();
+        decoder.apng().into_frames().collect_frames().unwrap()
+    }
+
+    fn gif_frames(gif_data: &[u8]) -> Vec<Frame> {
+        let decoder = GifDecoder::new(Cursor::new(gif_data)).unwrap();
+        decoder.into_frames().collect_frames().unwrap()
+    }
+
+    #[test]
+    fn apng_frames_are_composited_before_antialiasing() {
+        let input_data = encode_apng(&[
+            (staircase_rgba8(), (0, 0), DisposeOp::None, BlendOp::Over),
+            (half_transparent_rgba8(), (4, 4), DisposeOp::None, BlendOp::Over),
+        ]);
+        let mlaa_options = MlaaOptions::default();
+
+        let output_data = process_apng(&input_data, &mlaa_options, &ImageMetadata::default()).unwrap();
+
+        let input_frames = apng_frames(&input_data);
+        let output_frames = apng_frames(&output_data);
+        assert_eq!(output_frames.len(), 2);
+
+        for (input_frame, output_frame) in input_frames.iter().zip(&output_frames) {
+            assert_eq!(
+                output_frame.buffer(),
+                &mlaa_image_buffer(input_frame.buffer(), &mlaa_options)
+            );
+        }
+        assert_ne!(output_frames[1].buffer(), input_frames[1].buffer());
+        assert_eq!(output_frames[0].buffer(), output_frames[1].buffer());
+    }
+
+    #[test]
+    fn gif_frames_are_composited_before_antialiasing() {
+        // Black, white, gray and transparent.
+        let palette = [0, 0, 0, 255, 255, 255, 128, 128, 128, 0, 0, 0];
+        let mut input_data = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut input_data, 8, 8, &palette).unwrap();
+
+            let indices = staircase_rgba8()
+                .pixels()
+                .map(|p| if p[0] == 0 { 0 } else { 1 })
+                .collect::<Vec<u8>>();
+            encoder
+                .write_frame(&gif::Frame {
+                    width: 8,
+                    height: 8,
+                    buffer: indices.into(),
+                    ..gif::Frame::default()
+                })
+                .unwrap();
+
+            let indices = half_transparent_rgba8()
+                .pixels()
+                .map(|p| if p[3] == 0 { 3 } else { 1 })
+                .collect::<Vec<u8>>();
+            encoder
+                .write_frame(&gif::Frame {
+                    left: 4,
+                    top: 4,
+                    width: 4,
+                    height: 4,
+                    transparent: Some(3),
+                    buffer: indices.into(),
+                    ..gif::Frame::default()
+                })
+                .unwrap();
+        }
+
+        let (output_data, _) = process_gif(&input_data, &MlaaOptions::default()).unwrap();
+        let output_frames = gif_frames(&output_data);
+        assert_eq!(output_frames.len(), 2);
+
+        assert_ne!(output_frames[0].buffer(), &staircase_rgba8());
+        assert_eq!(output_frames[0].buffer(), output_frames[1].buffer());
+        assert!(output_frames[1].buffer().pixels().all(|p| p[3] == 255));
+    }
+
+    #[test]
+    fn sixteen_bit_apng_keeps_its_depth() {
+        let mut input_data = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut input_data, 8, 8);
+            encoder.set_color(png::ColorType::Grayscale);
+            encoder.set_depth(BitDepth::Sixteen);
+            encoder.set_animated(2, 0).unwrap();
+
+            let mut writer = encoder.write_header().unwrap();
+            let frame_data = staircase_rgba8()
+                .pixels()
+                .flat_map(|p| [p[0], p[0]])
+                .collect::<Vec<u8>>();
+            writer.write_image_data(&frame_data).unwrap();
+            writer.write_image_data(&frame_data).unwrap();
+            writer.finish().unwrap();
+        }
+
+        let output_data = process_apng(&input_data, &MlaaOptions::default(), &ImageMetadata::default()).unwrap();
+
+        let mut decoder = png::Decoder::new(Cursor::new(&output_data));
+        decoder.set_transformations(Transformations::IDENTITY);
+        let reader = decoder.read_info().unwrap();
+        assert_eq!(reader.output_color_type(), (png::ColorType::Rgba, BitDepth::Sixteen));
+        assert_eq!(
+            reader.info().animation_control.map(|control| control.num_frames),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn disposed_frames_are_removed_from_the_canvas() {
+        let white = RgbaImage::from_pixel(4, 4, Rgba([255; 4]));
+        let black = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 0, 255]));
+        let transparent = RgbaImage::new(1, 1);
+
+        let mut canvas = Canvas::<u8>::new(4, 4);
+        canvas.draw_frame(&white, (0, 0), true, Disposal::Keep).unwrap();
+        canvas.draw_frame(&black, (1, 1), true, Disposal::Previous).unwrap();
+        assert_eq!(canvas.image.get_pixel(1, 1), &Rgba([0, 0, 0, 255]));
+
+        canvas.draw_frame(&transparent, (0, 0), true, Disposal::Keep).unwrap();
+        assert_eq!(canvas.image, white);
+
+        canvas.draw_frame(&black, (1, 1), false, Disposal::Background).unwrap();
+        canvas.draw_frame(&transparent, (0, 0), true, Disposal::Keep).unwrap();
+        assert_eq!(canvas.image.get_pixel(1, 1), &Rgba([0; 4]));
+        assert_eq!(canvas.image.get_pixel(0, 0), &Rgba([255; 4]));
+
+        assert!(canvas.draw_frame(&black, (3, 3), true, Disposal::Keep).is_err());
+    }
+
+    #[test]
+    fn apng_frame_controls_are_kept() {
+        let black = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 0, 255]));
+        let input_data = encode_apng(&[
+            (staircase_rgba8(), (0, 0), DisposeOp::None, BlendOp::Source),
+            (half_transparent_rgba8(), (4, 4), DisposeOp::Previous, BlendOp::Over),
+            (black.clone(), (1, 5), DisposeOp::Background, BlendOp::Source),
+            (black, (5, 1), DisposeOp::None, BlendOp::Over),
+        ]);
+
+        let output_data = process_apng(&input_data, &MlaaOptions::default(), &ImageMetadata::default()).unwrap();
+        assert_eq!(apng_frame_controls(&output_data), apng_frame_controls(&input_data));
+    }
+
+    #[test]
+    fn gif_frame_fields_are_kept() {
+        let palette = [0, 0, 0, 255, 255, 255, 0, 0, 0];
+        let mut input_data = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut input_data, 8, 8, &palette).unwrap();
+
+            let indices = staircase_rgba8()
+                .pixels()
+                .map(|p| if p[0] == 0 { 0 } else { 1 })
+                .collect::<Vec<u8>>();
+            encoder
+                .write_frame(&gif::Frame {
+                    width: 8,
+                    height: 8,
+                    dispose: gif::DisposalMethod::Keep,
+                    buffer: indices.into(),
+                    ..gif::Frame::default()
+                })
+                .unwrap();
+
+            // Frames with their own palettes, together they use more colors
+            // than one palette can hold.
+            for (index, (left, top, dispose)) in [
+                (4, 4, gif::DisposalMethod::Previous),
+                (1, 5, gif::DisposalMethod::Background),
+                (5, 1, gif::DisposalMethod::Any),
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                let local_palette = (0..200u32)
+                    .flat_map(|color| [(color + index as u32 * 50) as u8, index as u8, color as u8])
+                    .collect::<Vec<u8>>();
+                encoder
+                    .write_frame(&gif::Frame {
+                        left,
+                        top,
+                        width: 3,
+                        height: 2,
+                        dispose,
+                        transparent: Some(199),
+                        palette: Some(local_palette),
+                        buffer: vec![0, 199, 0, 199, 0, 12].into(),
+                        ..gif::Frame::default()
+                    })
+                    .unwrap();
+            }
+        }
+
+        let (output_data, _) = process_gif(&input_data, &MlaaOptions::default()).unwrap();
+        assert_eq!(gif_frame_fields(&output_data), gif_frame_fields(&input_data));
+    }
+}
@@ -0,0 +1,666 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use image::ImageFormat;
+use rayon::prelude::*;
+
+use crate::cache::{cache_key, content_hash, Cache, CacheEntry};
+use crate::config::resolve_config;
+use crate::ora::{check_ora_output, is_ora, is_ora_path, ora_layer_jobs, process_ora};
+use crate::pipeline::{detect_image_format, process_image};
+use crate::provenance::Provenance;
+use crate::ProcessArgs;
+
+const DEFAULT_OUTPUT_TEMPLATE: &str = "{dir}/{stem}.{ext}";
+
+pub struct BatchJob {
+    pub input_path: PathBuf,
+    pub output_path: PathBuf,
+}
+
+struct InputFile {
+    path: PathBuf,
+    // Directory of the file relative to the directory given on the command
+    // line, recreated under the output directory.
+    relative_directory: PathBuf,
+    sequence_number: Option<usize>,
+}
+
+// Numbered image sequences are given with printf-style patterns such as
+// `frame_%04d.png`, the same way as in ffmpeg.
+struct SequencePattern {
+    prefix: String,
+    width: usize,
+    suffix: String,
+}
+
+impl SequencePattern {
+    // Only `%d` and `%0Nd` followed by a non-alphanumeric character are
+    // placeholders, any other `%` is part of the file name, as in
+    // `100%done.png`.
+    fn parse(path: &Path) -> Option<SequencePattern> {
+        let path = path.to_str()?;
+
+        path.match_indices('%').find_map(|(index, _)| {
+            let rest = &path[index + 1..];
+            let (width, suffix) = rest.split_at(rest.find(|c: char| !c.is_ascii_digit())?);
+            let suffix = suffix
+                .strip_prefix('d')
+                .filter(|suffix| !suffix.starts_with(|c: char| c.is_alphanumeric()))?;
+
+            let width = if width.is_empty() {
+                0
+            } else if width.starts_with('0') {
+                width.parse().ok()?
+            } else {
+                return None;
+            };
+
+            Some(SequencePattern {
+                prefix: path[..index].to_owned(),
+                width,
+                suffix: suffix.to_owned(),
+            })
+        })
+    }
+
+    fn path(&self, sequence_number: usize) -> PathBuf {
+        PathBuf::from(format!(
+            "{}{:0width$}{}",
+            self.prefix,
+            sequence_number,
+            self.suffix,
+            width = self.width
+        ))
+    }
+}
+
+enum JobOutcome {
+    Processed(CacheEntry),
+    Skipped,
+}
+
+pub fn is_glob_pattern(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| path.contains(['*', '?', '[']))
+}
+
+pub fn is_sequence_pattern(path: &Path) -> bool {
+    SequencePattern::parse(path).is_some()
+}
+
+pub fn is_batch(args: &ProcessArgs) -> bool {
+    args.input_paths.len() > 1
+        || args.output_dir.is_some()
+        || args.output_template.is_some()
+        || args.input_paths.iter().any(|input_path| {
+            input_path.is_dir()
+                || (!input_path.exists() && (is_glob_pattern(input_path) || is_sequence_pattern(input_path)))
+        })
+}
+
+fn collect_input_files(args: &ProcessArgs) -> Result<Vec<InputFile>, Box<dyn Error>> {
+    fn collect_directory(
+        root: &Path,
+        directory: &Path,
+        recursive: bool,
+        input_files: &mut Vec<InputFile>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut entries = fs::read_dir(directory)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+
+        for path in entries {
+            if path.is_dir() {
+                if recursive {
+                    collect_directory(root, &path, recursive, input_files)?;
+                }
+            } else if ImageFormat::from_path(&path).is_ok() || is_ora_path(&path) {
+                input_files.push(InputFile {
+                    path,
+                    relative_directory: directory.strip_prefix(root)?.to_owned(),
+                    sequence_number: None,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    let mut input_files = Vec::new();
+
+    // Existing files are taken literally, even if their names look like
+    // patterns.
+    for input_path in &args.input_paths {
+        if input_path.is_file() {
+            input_files.push(InputFile {
+                path: input_path.clone(),
+                relative_directory: PathBuf::new(),
+                sequence_number: None,
+            });
+        } else if input_path.is_dir() {
+            collect_directory(input_path, input_path, args.recursive, &mut input_files)?;
+        } else if let Some(sequence_pattern) = SequencePattern::parse(input_path) {
+            // Like in ffmpeg, the sequence starts at the first existing number
+            // of 0..=4 and ends before the first missing number.
+            let first_number = (0..=4)
+                .find(|&sequence_number| sequence_pattern.path(sequence_number).is_file())
+                .ok_or_else(|| format!("No files match \"{}\"", input_path.display()))?;
+
+            for sequence_number in first_number.. {
+                let path = sequence_pattern.path(sequence_number);
+                if !path.is_file() {
+                    break;
+                }
+
+                input_files.push(InputFile {
+                    path,
+                    relative_directory: PathBuf::new(),
+                    sequence_number: Some(sequence_number),
+                });
+            }
+        } else if is_glob_pattern(input_path) {
+            let pattern = input_path.to_str().unwrap();
+            let input_count = input_files.len();
+
+            for path in glob::glob(pattern)? {
+                let path = path?;
+                if path.is_file() {
+                    input_files.push(InputFile {
+                        path,
+                        relative_directory: PathBuf::new(),
+                        sequence_number: None,
+                    });
+                }
+            }
+
+            if input_files.len() == input_count {
+                return Err(format!("No files match \"{}\"", pattern).into());
+            }
+        } else {
+            input_files.push(InputFile {
+                path: input_path.clone(),
+                relative_directory: PathBuf::new(),
+                sequence_number: None,
+            });
+        }
+    }
+
+    Ok(input_files)
+}
+
+fn output_path(input_file: &InputFile, args: &ProcessArgs) -> Result<PathBuf, Box<dyn Error>> {
+    let input_path = &input_file.path;
+
+    if let Some(output_pattern) = args.output_path.as_deref().and_then(SequencePattern::parse) {
+        let sequence_number = input_file.sequence_number.ok_or_else(|| {
+            format!(
+                "\"{}\" is not part of an image sequence, it can't be written to an --output sequence pattern",
+                input_path.display()
+            )
+        })?;
+
+        return Ok(output_pattern.path(sequence_number));
+    }
+
+    let directory = if let Some(output_dir) = args.output_dir.as_ref() {
+        output_dir
+            .components()
+            .chain(input_file.relative_directory.components())
+            .collect()
+    } else {
+        input_path.parent().unwrap_or(Path::new("")).to_owned()
+    };
+
+    let directory = if directory.as_os_str().is_empty() {
+        Path::new(".").to_owned()
+    } else {
+        directory
+    };
+
+    let stem = input_path.file_stem().unwrap_or_default().to_string_lossy();
+
+    let extension = if let Some(output_format) = args.output_format {
+        output_format.extensions_str()[0].into()
+    } else {
+        input_path.extension().unwrap_or_default().to_string_lossy()
+    };
+
+    let template = args.output_template.as_deref().unwrap_or(DEFAULT_OUTPUT_TEMPLATE);
+
+    Ok(PathBuf::from(
+        template
+            .replace("{dir}", &directory.to_string_lossy())
+            .replace("{stem}", &stem)
+            .replace("{ext}", &extension),
+    ))
+}
+
+pub fn collect_jobs(args: &ProcessArgs) -> Result<Vec<BatchJob>, Box<dyn Error>> {
+    // Image sequences can be written to an output sequence pattern.
+    let has_output_pattern = args.output_path.as_deref().is_some_and(is_sequence_pattern);
+
+    if args.output_overlay_path.is_some() {
+        return Err("--output-overlay only works with a single input file".into());
+    }
+
+    if args.debug_args.debug_output_path.is_some() {
+        return Err("--debug-output only works with a single input file".into());
+    }
+
+    if args.output_path.is_some() && !has_output_pattern {
+        return Err("--output only works with a single input file, use --output-dir or --output-template".into());
+    }
+
+    if !has_output_pattern && args.output_dir.is_none() && args.output_template.is_none() {
+        return Err("Processing multiple files needs either --output-dir or --output-template".into());
+    }
+
+    let mut jobs = Vec::new();
+    let mut output_paths = HashSet::new();
+
+    let input_files = collect_input_files(args)?
+        .into_iter()
+        .map(|input_file| -> Result<_, Box<dyn Error>> {
+            let output_path = output_path(&input_file, args)?;
+            Ok((input_file.path, output_path))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Outputs of earlier runs written next to or below the inputs are not
+    // processed again.
+    let earlier_outputs = input_files
+        .iter()
+        .map(|(_, output_path)| output_path.clone())
+        .collect::<HashSet<_>>();
+
+    for (input_path, output_path) in input_files {
+        if output_path == input_path {
+            return Err(format!(
+                "Output file for \"{}\" would overwrite the input file",
+                input_path.display()
+            )
+            .into());
+        }
+
+        if earlier_outputs.contains(&input_path) {
+            continue;
+        }
+
+        if !output_paths.insert(output_path.clone()) {
+            return Err(format!("Multiple input files would be written to \"{}\"", output_path.display()).into());
+        }
+
+        jobs.push(BatchJob {
+            input_path,
+            output_path,
+        });
+    }
+
+    Ok(jobs)
+}
+
+fn run_job(job: &BatchJob, args: &ProcessArgs, cache: Option<&Cache>) -> Result<JobOutcome, Box<dyn Error>> {
+    let resolved_config = resolve_config(Some(&job.input_path), args.config_path.as_deref(), &args.option_args)?;
+    for warning in &resolved_config.warnings {
+        eprintln!("mlaa_image: {}", warning);
+    }
+
+    let input_data = fs::read(&job.input_path)?;
+
+    // ORA files are processed layer by layer, every layer has its own options.
+    let (output_format, ora_layer_jobs) = if is_ora(&input_data) {
+        check_ora_output(Some(&job.output_path), args)?;
+        (
+            None,
+            Some(ora_layer_jobs(
+                &input_data,
+                Some(&job.input_path),
+                &resolved_config,
+                args,
+            )?),
+        )
+    } else if let Some(output_format) = args.output_format {
+        (Some(output_format), None)
+    } else {
+        (Some(ImageFormat::from_path(&job.output_path)?), None)
+    };
+
+    let format_options = if let Some(ora_layer_jobs) = ora_layer_jobs.as_ref() {
+        let mut format_options = format!("ora_overlay = {}\n", args.ora_overlay);
+        for layer_job in ora_layer_jobs {
+            format_options += &format!(
+                "[layer \"{}\"]\n{}",
+                layer_job.layer_path,
+                Provenance::new(&layer_job.mlaa_options, layer_job.config_paths.clone()).to_toml()?
+            );
+        }
+        format_options
+    } else {
+        format!(
+            "input_format = {:?}\noutput_format = {:?}\n",
+            args.input_format,
+            output_format.unwrap()
+        )
+    };
+
+    // Everything besides the input content which ends up in the output file
+    let input_hash = content_hash(&input_data);
+    let options_hash = content_hash(
+        format!(
+            "{}\nkeep_palette = {}\nstrip_metadata = {}\n{}",
+            Provenance::new(&resolved_config.options, resolved_config.config_paths.clone()).to_toml()?,
+            args.keep_palette,
+            args.strip_metadata,
+            format_options,
+        )
+        .as_bytes(),
+    );
+
+    if let Some(cache) = cache {
+        if !args.force && cache.is_up_to_date(&job.output_path, &input_hash, &options_hash) {
+            return Ok(JobOutcome::Skipped);
+        }
+    }
+
+    let output_data = if let Some(ora_layer_jobs) = ora_layer_jobs {
+        process_ora(&input_data, &ora_layer_jobs, args)?
+    } else {
+        let input_format = detect_image_format(&input_data, args.input_format, Some(&job.input_path))?;
+
+        process_image(
+            &input_data,
+            input_format,
+            output_format.unwrap(),
+            &resolved_config.options,
+            resolved_config.config_paths,
+            args,
+        )?
+    };
+
+    if let Some(output_directory) = job.output_path.parent() {
+        fs::create_dir_all(output_directory)?;
+    }
+    fs::write(&job.output_path, &output_data)?;
+
+    Ok(JobOutcome::Processed(CacheEntry {
+        input_hash,
+        options_hash,
+        output_hash: content_hash(&output_data),
+    }))
+}
+
+pub fn configure_thread_pool(args: &ProcessArgs) -> Result<(), Box<dyn Error>> {
+    if let Some(thread_count) = args.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(thread_count)
+            .build_global()?;
+    }
+    Ok(())
+}
+
+pub fn batch_command(args: &ProcessArgs) -> Result<ExitCode, Box<dyn Error>> {
+    let jobs = collect_jobs(args)?;
+    configure_thread_pool(args)?;
+
+    if run_batch(&jobs, args)? {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+// Processes the jobs in parallel and prints a per-file summary, returns
+// whether every job succeeded.
+pub fn run_batch(jobs: &[BatchJob], args: &ProcessArgs) -> Result<bool, Box<dyn Error>> {
+    let mut cache = if let Some(cache_path) = args.cache_path.as_ref() {
+        Some(Cache::load(cache_path)?)
+    } else {
+        None
+    };
+
+    // Errors are flattened into strings on the worker threads, boxed errors
+    // can't be sent across threads.
+    let results = jobs
+        .par_iter()
+        .map(|job| {
+            run_job(job, args, cache.as_ref())
+                .map_err(|err| err.sources().map(ToString::to_string).collect::<Vec<_>>().join(": "))
+        })
+        .collect::<Vec<_>>();
+
+    let (mut success_count, mut skip_count, mut failure_count) = (0, 0, 0);
+
+    for (job, result) in jobs.iter().zip(results) {
+        match result {
+            Ok(JobOutcome::Processed(cache_entry)) => {
+                eprintln!(
+                    "mlaa_image: ok      \"{}\" -> \"{}\"",
+                    job.input_path.display(),
+                    job.output_path.display()
+                );
+                if let Some(cache) = cache.as_mut() {
+                    cache.entries.insert(cache_key(&job.output_path), cache_entry);
+                }
+                success_count += 1;
+            }
+            Ok(JobOutcome::Skipped) => {
+                eprintln!("mlaa_image: skipped \"{}\" (unchanged)", job.input_path.display());
+                skip_count += 1;
+            }
+            Err(message) => {
+                eprintln!("mlaa_image: failed  \"{}\": {}", job.input_path.display(), message);
+                if let Some(cache) = cache.as_mut() {
+                    cache.entries.remove(&cache_key(&job.output_path));
+                }
+                failure_count += 1;
+            }
+        }
+    }
+
+    if let (Some(cache), Some(cache_path)) = (cache.as_ref(), args.cache_path.as_ref()) {
+        cache.save(cache_path)?;
+    }
+
+    eprintln!(
+        "mlaa_image: {} files, {} processed, {} skipped, {} failed",
+        jobs.len(),
+        success_count,
+        skip_count,
+        failure_count
+    );
+
+    Ok(failure_count == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::MlaaArgs;
+
+    fn process_args(args: &[&str]) -> ProcessArgs {
+        MlaaArgs::parse_from(["mlaa_image"].iter().chain(args)).process_args
+    }
+
+    fn sequence_pattern(path: &str) -> Option<(String, usize, String)> {
+        SequencePattern::parse(Path::new(path)).map(|pattern| (pattern.prefix, pattern.width, pattern.suffix))
+    }
+
+    #[test]
+    fn only_number_placeholders_are_sequence_patterns() {
+        let pattern = |prefix: &str, width, suffix: &str| Some((prefix.to_owned(), width, suffix.to_owned()));
+
+        assert_eq!(sequence_pattern("frame_%d.png"), pattern("frame_", 0, ".png"));
+        assert_eq!(sequence_pattern("frame_%04d.png"), pattern("frame_", 4, ".png"));
+        assert_eq!(sequence_pattern("50%_frame_%03d.png"), pattern("50%_frame_", 3, ".png"));
+        assert_eq!(sequence_pattern("frame_%d"), pattern("frame_", 0, ""));
+        assert_eq!(sequence_pattern("100%done.png"), None);
+        assert_eq!(sequence_pattern("frame_%4d.png"), None);
+        assert_eq!(sequence_pattern("frame_%s.png"), None);
+        assert_eq!(sequence_pattern("frame.png"), None);
+    }
+
+    #[test]
+    fn existing_files_are_not_patterns() {
+        let temp_dir = TempDir::new("batch-literal-names");
+        let bracket_path = temp_dir.write("shot[1].png", "");
+        let percent_path = temp_dir.write("100%done.png", "");
+        let sequence_path = temp_dir.write("frame_%d.png", "");
+
+        for input_path in [&bracket_path, &percent_path, &sequence_path] {
+            let args = process_args(&["-i", input_path.to_str().unwrap()]);
+            assert!(!is_batch(&args));
+
+            let input_files = collect_input_files(&args).unwrap();
+            assert_eq!(input_files.len(), 1);
+            assert_eq!(&input_files[0].path, input_path);
+        }
+    }
+
+    #[test]
+    fn same_output_directory_is_refused() {
+        let temp_dir = TempDir::new("batch-same-directory");
+        temp_dir.write("a.png", "");
+        temp_dir.write("b.png", "");
+        let directory = temp_dir.path().to_str().unwrap();
+
+        let err = collect_jobs(&process_args(&["-i", directory, "--output-dir", directory]))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("would overwrite the input file"), "{}", err);
+    }
+
+    #[test]
+    fn earlier_outputs_are_skipped() {
+        let temp_dir = TempDir::new("batch-earlier-outputs");
+        let input_path = temp_dir.write("a.png", "");
+        temp_dir.write("a_aa.png", "");
+        let directory = temp_dir.path().to_str().unwrap();
+
+        let jobs = collect_jobs(&process_args(&[
+            "-i",
+            directory,
+            "--output-template",
+            "{dir}/{stem}_aa.{ext}",
+        ]))
+        .unwrap();
+
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].input_path, input_path);
+        assert_eq!(jobs[0].output_path, temp_dir.path().join("a_aa.png"));
+    }
+
+    #[test]
+    fn separate_output_directory() {
+        let temp_dir = TempDir::new("batch-output-directory");
+        temp_dir.write("input/a.png", "");
+        temp_dir.write("input/b.png", "");
+        let input_directory = temp_dir.path().join("input");
+        let output_directory = temp_dir.path().join("output");
+
+        let jobs = collect_jobs(&process_args(&[
+            "-i",
+            input_directory.to_str().unwrap(),
+            "--output-dir",
+            output_directory.to_str().unwrap(),
+        ]))
+        .unwrap();
+
+        let mut output_paths = jobs.iter().map(|job| job.output_path.clone()).collect::<Vec<_>>();
+        output_paths.sort();
+        assert_eq!(
+            output_paths,
+            [output_directory.join("a.png"), output_directory.join("b.png")]
+        );
+    }
+
+    // Runs a single job against the cache written by an earlier batch run,
+    // returns whether it was skipped.
+    fn is_skipped(input_path: &Path, output_path: &Path, args: &[&str]) -> bool {
+        let args = process_args(args);
+        let job = BatchJob {
+            input_path: input_path.to_owned(),
+            output_path: output_path.to_owned(),
+        };
+        let cache = Cache::load(args.cache_path.as_ref().unwrap()).unwrap();
+
+        matches!(run_job(&job, &args, Some(&cache)).unwrap(), JobOutcome::Skipped)
+    }
+
+    #[test]
+    fn cache_skips_unchanged_files() {
+        let temp_dir = TempDir::new("batch-cache");
+        let input_path = temp_dir.path().join("input.png");
+        let output_path = temp_dir.path().join("output").join("input.png");
+        let cache_path = temp_dir.path().join("cache.toml");
+        image::RgbaImage::from_fn(4, 4, |x, _| image::Rgba([(x * 60) as u8, 0, 0, 255]))
+            .save(&input_path)
+            .unwrap();
+
+        let output_dir = temp_dir.path().join("output");
+        let base_args = [
+            "-i",
+            input_path.to_str().unwrap(),
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+            "--cache",
+            cache_path.to_str().unwrap(),
+        ];
+        let with_base_args = |args: &[&'static str]| {
+            base_args
+                .iter()
+                .copied()
+                .chain(args.iter().copied())
+                .collect::<Vec<_>>()
+        };
+
+        let run = || {
+            let args = process_args(&base_args);
+            assert!(run_batch(&collect_jobs(&args).unwrap(), &args).unwrap());
+        };
+
+        run();
+        assert!(is_skipped(&input_path, &output_path, &base_args));
+
+        // Changed options and formats
+        assert!(!is_skipped(
+            &input_path,
+            &output_path,
+            &with_base_args(&["--blend-space", "oklab"])
+        ));
+        run();
+        assert!(!is_skipped(
+            &input_path,
+            &output_path,
+            &with_base_args(&["--input-format", "png"])
+        ));
+        run();
+        assert!(!is_skipped(
+            &input_path,
+            &output_path,
+            &with_base_args(&["--output-format", "bmp"])
+        ));
+        run();
+        assert!(!is_skipped(&input_path, &output_path, &with_base_args(&["--force"])));
+
+        // Deleted output
+        run();
+        fs::remove_file(&output_path).unwrap();
+        assert!(!is_skipped(&input_path, &output_path, &base_args));
+
+        // Edited input
+        run();
+        assert!(is_skipped(&input_path, &output_path, &base_args));
+        image::RgbaImage::from_pixel(4, 4, image::Rgba([0, 0, 255, 255]))
+            .save(&input_path)
+            .unwrap();
+        assert!(!is_skipped(&input_path, &output_path, &base_args));
+    }
+}
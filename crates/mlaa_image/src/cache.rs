@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub input_hash: String,
+    pub options_hash: String,
+    pub output_hash: String,
+}
+
+// Maps the output paths of earlier batch runs to the hashes of the input
+// content, the effective options and the written output.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Cache {
+    #[serde(default)]
+    pub entries: BTreeMap<String, CacheEntry>,
+}
+
+impl Cache {
+    pub fn load(cache_path: &Path) -> Result<Cache, Box<dyn Error>> {
+        if cache_path.is_file() {
+            Ok(toml::from_str(&fs::read_to_string(cache_path)?)?)
+        } else {
+            Ok(Cache::default())
+        }
+    }
+
+    pub fn save(&self, cache_path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(cache_path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    // An output is up to date when its entry matches and the file on disk
+    // wasn't modified or removed since it was written.
+    pub fn is_up_to_date(&self, output_path: &Path, input_hash: &str, options_hash: &str) -> bool {
+        let Some(entry) = self.entries.get(&cache_key(output_path)) else {
+            return false;
+        };
+
+        (entry.input_hash == input_hash)
+            && (entry.options_hash == options_hash)
+            && fs::read(output_path).is_ok_and(|output_data| content_hash(&output_data) == entry.output_hash)
+    }
+}
+
+pub fn cache_key(output_path: &Path) -> String {
+    output_path.to_string_lossy().into_owned()
+}
+
+pub fn content_hash(data: &[u8]) -> String {
+    blake3::hash(data).to_hex().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn outputs_are_up_to_date_until_something_changes() {
+        let temp_dir = TempDir::new("cache-up-to-date");
+        let output_path = temp_dir.write("output.png", "output");
+
+        let mut cache = Cache::default();
+        cache.entries.insert(
+            cache_key(&output_path),
+            CacheEntry {
+                input_hash: content_hash(b"input"),
+                options_hash: content_hash(b"options"),
+                output_hash: content_hash(b"output"),
+            },
+        );
+
+        let cache_path = temp_dir.path().join("cache.toml");
+        cache.save(&cache_path).unwrap();
+        let cache = Cache::load(&cache_path).unwrap();
+
+        let (input_hash, options_hash) = (content_hash(b"input"), content_hash(b"options"));
+        assert!(cache.is_up_to_date(&output_path, &input_hash, &options_hash));
+        assert!(!cache.is_up_to_date(&output_path, &content_hash(b"edited input"), &options_hash));
+        assert!(!cache.is_up_to_date(&output_path, &input_hash, &content_hash(b"other options")));
+        assert!(!cache.is_up_to_date(&temp_dir.path().join("other.png"), &input_hash, &options_hash));
+
+        temp_dir.write("output.png", "edited output");
+        assert!(!cache.is_up_to_date(&output_path, &input_hash, &options_hash));
+
+        fs::remove_file(&output_path).unwrap();
+        assert!(!cache.is_up_to_date(&output_path, &input_hash, &options_hash));
+    }
+}
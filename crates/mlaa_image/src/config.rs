@@ -0,0 +1,900 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::iter;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use glob::{MatchOptions, Pattern};
+use mlaa_impl::{BlendSpace, BrightnessMetric, MlaaOptions};
+use serde::Deserialize;
+use toml::Spanned;
+
+pub const CONFIG_FILE_NAME: &str = ".mlaa";
+pub const CONFIG_ENV_VAR: &str = "MLAA_CONFIG";
+
+// `*` doesn't match across directories in rule patterns, `**` has to be used
+// for that.
+pub const RULE_MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+#[derive(Clone)]
+pub enum ConfigSource {
+    Defaults,
+    UserConfig(PathBuf),
+    DirectoryConfig(PathBuf),
+    Environment(PathBuf),
+    CommandLine(PathBuf),
+    CommandLineFlags,
+}
+
+impl ConfigSource {
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ConfigSource::Defaults | ConfigSource::CommandLineFlags => None,
+            ConfigSource::UserConfig(path)
+            | ConfigSource::DirectoryConfig(path)
+            | ConfigSource::Environment(path)
+            | ConfigSource::CommandLine(path) => Some(path),
+        }
+    }
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Defaults => write!(f, "built-in defaults"),
+            ConfigSource::UserConfig(path) => write!(f, "{} (user config)", path.display()),
+            ConfigSource::DirectoryConfig(path) => write!(f, "{}", path.display()),
+            ConfigSource::Environment(path) => write!(f, "{} (${})", path.display(), CONFIG_ENV_VAR),
+            ConfigSource::CommandLine(path) => write!(f, "{} (--config)", path.display()),
+            ConfigSource::CommandLineFlags => write!(f, "command line flags"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ValueSource {
+    pub config_source: ConfigSource,
+    pub rule_pattern: Option<String>,
+    pub preset_name: Option<String>,
+}
+
+impl fmt::Display for ValueSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.config_source)?;
+        if let Some(rule_pattern) = self.rule_pattern.as_ref() {
+            write!(f, ", rule \"{}\"", rule_pattern)?;
+        }
+        if let Some(preset_name) = self.preset_name.as_ref() {
+            write!(f, ", preset \"{}\"", preset_name)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct ResolvedConfig {
+    pub options: MlaaOptions,
+    pub values: toml::Table,
+    pub value_sources: BTreeMap<String, ValueSource>,
+    pub config_paths: Vec<PathBuf>,
+    pub warnings: Vec<String>,
+    // Patterns of the rules matching the layer path, for layers of ORA files.
+    pub layer_rule_patterns: Vec<String>,
+}
+
+// Command line counterparts of the `MlaaOptions` fields, these override every
+// config file.
+#[derive(Args)]
+pub struct OptionArgs {
+    /// Named preset the other options are applied on top of
+    #[clap(long = "preset")]
+    preset: Option<String>,
+
+    /// Smooths the steps of vertical edges
+    #[clap(long = "vertical-smoothing", overrides_with = "no_vertical_smoothing")]
+    vertical_smoothing: bool,
+    /// Leaves the steps of vertical edges unchanged
+    #[clap(long = "no-vertical-smoothing")]
+    no_vertical_smoothing: bool,
+
+    /// Smooths the steps of horizontal edges
+    #[clap(long = "horizontal-smoothing", overrides_with = "no_horizontal_smoothing")]
+    horizontal_smoothing: bool,
+    /// Leaves the steps of horizontal edges unchanged
+    #[clap(long = "no-horizontal-smoothing")]
+    no_horizontal_smoothing: bool,
+
+    /// Smooths single pixel corners
+    #[clap(long = "corner-smoothing", overrides_with = "no_corner_smoothing")]
+    corner_smoothing: bool,
+    /// Leaves single pixel corners unchanged
+    #[clap(long = "no-corner-smoothing")]
+    no_corner_smoothing: bool,
+
+    /// Only joins seams whose neighbor has exactly the same two colors
+    #[clap(long = "strict-mode", overrides_with = "no_strict_mode")]
+    strict_mode: bool,
+    /// Also joins seams whose neighbor shares only one of their colors
+    #[clap(long = "no-strict-mode")]
+    no_strict_mode: bool,
+
+    /// Where the gradients start along a seam, from 0.0 at its middle to 1.0 at its end
+    #[clap(long = "seam-split-position")]
+    seam_split_position: Option<f64>,
+
+    /// Skips seams whose brightness order differs from their neighbor
+    #[clap(long = "seam-brightness-balance", overrides_with = "no_seam_brightness_balance")]
+    seam_brightness_balance: bool,
+    /// Joins seams regardless of their brightness order
+    #[clap(long = "no-seam-brightness-balance")]
+    no_seam_brightness_balance: bool,
+
+    /// Color space the gradients are blended in
+    #[clap(long = "blend-space", value_enum)]
+    blend_space: Option<BlendSpace>,
+
+    /// Brightness measure used to compare the colors of seams
+    #[clap(long = "brightness-metric", value_enum)]
+    brightness_metric: Option<BrightnessMetric>,
+}
+
+impl OptionArgs {
+    pub fn to_table(&self) -> toml::Table {
+        let flag_pairs = [
+            (
+                "vertical_smoothing",
+                self.vertical_smoothing,
+                self.no_vertical_smoothing,
+            ),
+            (
+                "horizontal_smoothing",
+                self.horizontal_smoothing,
+                self.no_horizontal_smoothing,
+            ),
+            ("corner_smoothing", self.corner_smoothing, self.no_corner_smoothing),
+            ("strict_mode", self.strict_mode, self.no_strict_mode),
+            (
+                "seam_brigtness_balance",
+                self.seam_brightness_balance,
+                self.no_seam_brightness_balance,
+            ),
+        ];
+
+        let mut values = toml::Table::new();
+
+        if let Some(preset) = self.preset.as_ref() {
+            values.insert("preset".to_owned(), toml::Value::String(preset.clone()));
+        }
+
+        for (key, enabled, disabled) in flag_pairs {
+            if enabled || disabled {
+                values.insert(key.to_owned(), toml::Value::Boolean(enabled));
+            }
+        }
+
+        if let Some(seam_split_position) = self.seam_split_position {
+            values.insert(
+                "seam_split_position".to_owned(),
+                toml::Value::Float(seam_split_position),
+            );
+        }
+
+        if let Some(blend_space) = self.blend_space {
+            values.insert("blend_space".to_owned(), blend_space.name().into());
+        }
+
+        if let Some(brightness_metric) = self.brightness_metric {
+            values.insert("brightness_metric".to_owned(), brightness_metric.name().into());
+        }
+
+        values
+    }
+}
+
+struct ConfigRule {
+    pattern: Pattern,
+    values: toml::Table,
+}
+
+struct ConfigLayer {
+    values: toml::Table,
+    rules: Vec<ConfigRule>,
+}
+
+// Where the config files outside of the input directories are looked up,
+// directory configs are only searched below `root` when it is given.
+pub struct ConfigEnv {
+    pub config_home: Option<PathBuf>,
+    pub env_config_path: Option<PathBuf>,
+    pub root: Option<PathBuf>,
+}
+
+impl ConfigEnv {
+    pub fn from_process() -> ConfigEnv {
+        ConfigEnv {
+            config_home: env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .filter(|config_home| config_home.is_absolute())
+                .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))),
+            env_config_path: env::var_os(CONFIG_ENV_VAR)
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            root: None,
+        }
+    }
+
+    fn user_config_path(&self) -> Option<PathBuf> {
+        Some(self.config_home.as_ref()?.join("mlaa").join("config.toml"))
+    }
+}
+
+// Lists the config files applying to the input, from the lowest to the
+// highest priority. Directory configs are searched from the root down to the
+// directory of the input.
+pub fn config_sources(
+    config_env: &ConfigEnv,
+    input_path: Option<&Path>,
+    explicit_config_path: Option<&Path>,
+) -> Vec<ConfigSource> {
+    let mut config_sources = vec![ConfigSource::Defaults];
+
+    if let Some(user_config_path) = config_env.user_config_path().filter(|path| path.is_file()) {
+        config_sources.push(ConfigSource::UserConfig(user_config_path));
+    }
+
+    if let Some(input_path) = input_path {
+        let search_directory = if input_path.is_dir() {
+            input_path
+        } else {
+            input_path.parent().unwrap_or(Path::new(""))
+        };
+
+        let search_directory = if search_directory.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            search_directory
+        };
+
+        let search_directory = fs::canonicalize(search_directory).unwrap_or_else(|_| search_directory.to_owned());
+
+        let mut directory_configs = search_directory
+            .ancestors()
+            .take_while(|directory| match &config_env.root {
+                Some(root) => directory.starts_with(root),
+                None => true,
+            })
+            .map(|directory| directory.join(CONFIG_FILE_NAME))
+            .filter(|config_path| config_path.is_file())
+            .map(ConfigSource::DirectoryConfig)
+            .collect::<Vec<_>>();
+        directory_configs.reverse();
+
+        config_sources.extend(directory_configs);
+    }
+
+    if let Some(env_config_path) = config_env.env_config_path.as_ref() {
+        config_sources.push(ConfigSource::Environment(env_config_path.clone()));
+    }
+
+    if let Some(explicit_config_path) = explicit_config_path {
+        config_sources.push(ConfigSource::CommandLine(explicit_config_path.to_owned()));
+    }
+
+    config_sources
+}
+
+// Canonicalizes paths which might not exist yet, like the inputs given to
+// `config --explain`.
+fn absolute_path(path: &Path) -> PathBuf {
+    if let Ok(path) = fs::canonicalize(path) {
+        path
+    } else if let (Some(parent), Some(file_name)) = (path.parent(), path.file_name()) {
+        absolute_path(if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        })
+        .join(file_name)
+    } else {
+        env::current_dir()
+            .map(|current_dir| current_dir.join(path))
+            .unwrap_or_else(|_| path.to_owned())
+    }
+}
+
+type SpannedTable = BTreeMap<Spanned<String>, Spanned<toml::Value>>;
+
+#[derive(Deserialize)]
+struct ConfigRules {
+    #[serde(default)]
+    rules: Vec<SpannedTable>,
+}
+
+// Correctly spelled names accepted for misspelled option fields.
+const OPTION_ALIASES: [(&str, &str); 1] = [("seam_brightness_balance", "seam_brigtness_balance")];
+
+fn text_position(text: &str, offset: usize) -> String {
+    let preceding_text = &text[..offset.min(text.len())];
+    let line = preceding_text.matches('\n').count() + 1;
+    let column = preceding_text.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    format!("line {}, column {}", line, column)
+}
+
+fn check_preset_name(preset_name: &toml::Value) -> Result<&str, String> {
+    match preset_name.as_str() {
+        Some(preset_name) if MlaaOptions::preset(preset_name).is_some() => Ok(preset_name),
+        _ => Err(format!(
+            "Unknown preset {}, expected one of {}",
+            preset_name,
+            MlaaOptions::PRESET_NAMES.map(|name| format!("\"{}\"", name)).join(", ")
+        )),
+    }
+}
+
+// Expands the `preset` key of a table into the values of the preset, the other
+// values of the table are ordered after and override them. Returns the preset
+// name along with each value taken from a preset.
+fn expand_preset(values: &toml::Table) -> Result<Vec<(String, toml::Value, Option<String>)>, String> {
+    let mut expanded_values = Vec::new();
+
+    if let Some(preset_name) = values.get("preset") {
+        let preset_name = check_preset_name(preset_name)?;
+        let preset_values =
+            toml::Table::try_from(MlaaOptions::preset(preset_name).unwrap()).map_err(|err| err.to_string())?;
+
+        for (key, value) in preset_values {
+            expanded_values.push((key, value, Some(preset_name.to_owned())));
+        }
+    }
+
+    for (key, value) in values {
+        if key != "preset" {
+            expanded_values.push((key.clone(), value.clone(), None));
+        }
+    }
+
+    Ok(expanded_values)
+}
+
+// Checks the option values one by one, so errors can point at the offending
+// key or value. Aliases are replaced with the actual field names.
+fn check_option_values(config_data: &str, values: SpannedTable) -> Result<toml::Table, String> {
+    let option_names = toml::Table::try_from(MlaaOptions::default()).map_err(|err| err.to_string())?;
+
+    let mut checked_values = toml::Table::new();
+
+    for (key, value) in values {
+        let name = OPTION_ALIASES
+            .iter()
+            .find(|(alias, _)| alias == key.get_ref())
+            .map_or(key.get_ref().as_str(), |(_, name)| name);
+
+        if name == "preset" {
+            check_preset_name(value.get_ref())
+                .map_err(|err| format!("{}: {}", text_position(config_data, value.span().start), err))?;
+            checked_values.insert(name.to_owned(), value.into_inner());
+            continue;
+        }
+
+        if !option_names.contains_key(name) {
+            return Err(format!(
+                "{}: Unknown option `{}`",
+                text_position(config_data, key.span().start),
+                key.get_ref()
+            ));
+        }
+
+        let option_value = toml::Table::from_iter([(name.to_owned(), value.get_ref().clone())]);
+        option_value
+            .try_into::<MlaaOptions>()
+            .map_err(|err| err.message().to_owned())
+            .and_then(|mlaa_options| mlaa_options.validate())
+            .map_err(|err| format!("{}: {}", text_position(config_data, value.span().start), err))?;
+
+        checked_values.insert(name.to_owned(), value.into_inner());
+    }
+
+    Ok(checked_values)
+}
+
+fn read_config_layer(config_source: &ConfigSource) -> Result<ConfigLayer, Box<dyn Error>> {
+    let Some(config_path) = config_source.path() else {
+        return Ok(ConfigLayer {
+            values: toml::Table::try_from(MlaaOptions::default())?,
+            rules: Vec::new(),
+        });
+    };
+
+    let invalid_config = |message: String| format!("Invalid config file \"{}\": {}", config_path.display(), message);
+
+    let config_data = fs::read_to_string(config_path)
+        .map_err(|err| format!("Failed to read config file \"{}\": {}", config_path.display(), err))?;
+
+    let mut values = toml::from_str::<SpannedTable>(&config_data).map_err(|err| invalid_config(err.to_string()))?;
+    values.remove("rules");
+    let values = check_option_values(&config_data, values).map_err(invalid_config)?;
+
+    let config_rules = toml::from_str::<ConfigRules>(&config_data).map_err(|err| invalid_config(err.to_string()))?;
+
+    let mut rules = Vec::new();
+
+    for mut rule_values in config_rules.rules {
+        let Some(pattern) = rule_values.remove("path") else {
+            return Err(invalid_config("Every rule needs a `path` glob pattern".to_owned()).into());
+        };
+
+        let pattern_position = text_position(&config_data, pattern.span().start);
+        let toml::Value::String(pattern) = pattern.into_inner() else {
+            return Err(invalid_config(format!("{}: Rule `path` must be a string", pattern_position)).into());
+        };
+
+        rules.push(ConfigRule {
+            pattern: Pattern::new(&pattern)
+                .map_err(|err| invalid_config(format!("{}: \"{}\": {}", pattern_position, pattern, err)))?,
+            values: check_option_values(&config_data, rule_values).map_err(invalid_config)?,
+        });
+    }
+
+    Ok(ConfigLayer { values, rules })
+}
+
+// Merges the config layers key by key, later layers override the values of
+// earlier ones. The rules of a config file are applied right after the file's
+// own values, their path patterns are relative to the directory of the file.
+// The command line flags are applied last.
+pub fn resolve_config(
+    input_path: Option<&Path>,
+    explicit_config_path: Option<&Path>,
+    option_args: &OptionArgs,
+) -> Result<ResolvedConfig, Box<dyn Error>> {
+    resolve_config_inner(
+        &ConfigEnv::from_process(),
+        input_path,
+        None,
+        explicit_config_path,
+        option_args,
+    )
+}
+
+// Layers of ORA files are matched against the rules as paths below the ORA
+// file, like `artwork.ora/group/layer`. The rules matching the ORA file itself
+// are applied first, the ones matching the layer path override them.
+pub fn resolve_layer_config(
+    input_path: Option<&Path>,
+    layer_path: &str,
+    explicit_config_path: Option<&Path>,
+    option_args: &OptionArgs,
+) -> Result<ResolvedConfig, Box<dyn Error>> {
+    resolve_config_inner(
+        &ConfigEnv::from_process(),
+        input_path,
+        Some(layer_path),
+        explicit_config_path,
+        option_args,
+    )
+}
+
+fn resolve_config_inner(
+    config_env: &ConfigEnv,
+    input_path: Option<&Path>,
+    layer_path: Option<&str>,
+    explicit_config_path: Option<&Path>,
+    option_args: &OptionArgs,
+) -> Result<ResolvedConfig, Box<dyn Error>> {
+    let mut values = toml::Table::new();
+    let mut value_sources = BTreeMap::new();
+    let mut config_paths = Vec::new();
+    let mut warnings = Vec::new();
+    let mut layer_rule_patterns = Vec::new();
+
+    let input_path = input_path.map(absolute_path);
+
+    let option_overrides = option_args.to_table();
+
+    let config_sources = config_sources(config_env, input_path.as_deref(), explicit_config_path)
+        .into_iter()
+        .chain((!option_overrides.is_empty()).then_some(ConfigSource::CommandLineFlags));
+
+    for config_source in config_sources {
+        let config_layer = if let ConfigSource::CommandLineFlags = config_source {
+            ConfigLayer {
+                values: option_overrides.clone(),
+                rules: Vec::new(),
+            }
+        } else {
+            read_config_layer(&config_source)?
+        };
+
+        for (key, value, preset_name) in expand_preset(&config_layer.values)? {
+            value_sources.insert(
+                key.clone(),
+                ValueSource {
+                    config_source: config_source.clone(),
+                    rule_pattern: None,
+                    preset_name,
+                },
+            );
+            values.insert(key, value);
+        }
+
+        if let (Some(input_path), Some(config_path)) = (input_path.as_ref(), config_source.path()) {
+            let config_directory = absolute_path(config_path)
+                .parent()
+                .map(Path::to_owned)
+                .unwrap_or_default();
+            let rule_paths =
+                iter::once(input_path.clone()).chain(layer_path.map(|layer_path| input_path.join(layer_path)));
+
+            for (rule_path, is_layer_path) in rule_paths.zip([false, true]) {
+                let relative_rule_path = rule_path.strip_prefix(&config_directory).unwrap_or(&rule_path);
+
+                let mut rule_values = BTreeMap::<String, (&str, toml::Value)>::new();
+
+                for rule in &config_layer.rules {
+                    if !rule.pattern.matches_path_with(relative_rule_path, RULE_MATCH_OPTIONS) {
+                        continue;
+                    }
+
+                    if is_layer_path {
+                        layer_rule_patterns.push(rule.pattern.as_str().to_owned());
+                    }
+
+                    for (key, value, preset_name) in expand_preset(&rule.values)? {
+                        if let Some((earlier_pattern, earlier_value)) = rule_values.get(&key) {
+                            if *earlier_value != value {
+                                warnings.push(format!(
+                                    "Conflicting rules \"{}\" and \"{}\" in \"{}\" for \"{}\", `{}` is set to {} instead of {}",
+                                    earlier_pattern,
+                                    rule.pattern.as_str(),
+                                    config_path.display(),
+                                    rule_path.display(),
+                                    key,
+                                    value,
+                                    earlier_value,
+                                ));
+                            }
+                        }
+                        rule_values.insert(key.clone(), (rule.pattern.as_str(), value.clone()));
+
+                        value_sources.insert(
+                            key.clone(),
+                            ValueSource {
+                                config_source: config_source.clone(),
+                                rule_pattern: Some(rule.pattern.as_str().to_owned()),
+                                preset_name,
+                            },
+                        );
+                        values.insert(key, value);
+                    }
+                }
+            }
+        }
+
+        if let Some(config_path) = config_source.path() {
+            config_paths.push(config_path.to_owned());
+        }
+    }
+
+    let options = values
+        .clone()
+        .try_into::<MlaaOptions>()
+        .map_err(|err| err.message().to_owned())
+        .and_then(|options| options.validate().map(|()| options))?;
+
+    Ok(ResolvedConfig {
+        options,
+        values,
+        value_sources,
+        config_paths,
+        warnings,
+        layer_rule_patterns,
+    })
+}
+
+// JSON Schema of the config files, the generated `MlaaOptions` schema extended
+// with the `[[rules]]` sections.
+pub fn config_schema() -> Result<serde_json::Value, Box<dyn Error>> {
+    let mut schema = serde_json::to_value(schemars::schema_for!(MlaaOptions))?;
+
+    schema["properties"]["preset"] = serde_json::json!({
+        "description": "Named preset the other options of the table are applied on top of",
+        "enum": MlaaOptions::PRESET_NAMES,
+    });
+
+    let mut rule_properties = schema["properties"].clone();
+    rule_properties["path"] = serde_json::json!({
+        "description": "Glob pattern matched against the input paths relative to the config file",
+        "type": "string",
+    });
+
+    schema["properties"]["rules"] = serde_json::json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": rule_properties,
+            "required": ["path"],
+            "additionalProperties": false,
+        },
+    });
+
+    Ok(schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[derive(Parser)]
+    struct TestArgs {
+        #[clap(flatten)]
+        option_args: OptionArgs,
+    }
+
+    fn option_args(args: &[&str]) -> OptionArgs {
+        TestArgs::parse_from(iter::once("mlaa_image").chain(args.iter().copied())).option_args
+    }
+
+    // Only the config files below the temp directory are used, none of the
+    // machine running the tests.
+    fn test_env(temp_dir: &TempDir) -> ConfigEnv {
+        ConfigEnv {
+            config_home: None,
+            env_config_path: None,
+            root: Some(temp_dir.path().to_owned()),
+        }
+    }
+
+    fn resolve_config(
+        temp_dir: &TempDir,
+        input_path: Option<&Path>,
+        explicit_config_path: Option<&Path>,
+        option_args: &OptionArgs,
+    ) -> Result<ResolvedConfig, Box<dyn Error>> {
+        resolve_config_inner(&test_env(temp_dir), input_path, None, explicit_config_path, option_args)
+    }
+
+    #[test]
+    fn config_files_accept_preset_names() {
+        let temp_dir = TempDir::new("config-presets");
+
+        for preset_name in MlaaOptions::PRESET_NAMES {
+            let config_path = temp_dir.write("preset.mlaa", format!("preset = \"{}\"\n", preset_name));
+            let resolved_config = resolve_config(&temp_dir, None, Some(&config_path), &option_args(&[])).unwrap();
+
+            assert!(resolved_config.options == MlaaOptions::preset(preset_name).unwrap());
+            assert_eq!(
+                resolved_config.value_sources["strict_mode"].preset_name.as_deref(),
+                Some(preset_name)
+            );
+        }
+    }
+
+    #[test]
+    fn command_line_accepts_preset_names() {
+        let temp_dir = TempDir::new("config-command-line-presets");
+        for preset_name in MlaaOptions::PRESET_NAMES {
+            let resolved_config =
+                resolve_config(&temp_dir, None, None, &option_args(&["--preset", preset_name])).unwrap();
+            assert!(resolved_config.options == MlaaOptions::preset(preset_name).unwrap());
+        }
+    }
+
+    #[test]
+    fn unknown_preset_names_are_rejected() {
+        let temp_dir = TempDir::new("config-unknown-preset");
+        let config_path = temp_dir.write("preset.mlaa", "preset = \"sharp\"\n");
+
+        let err = resolve_config(&temp_dir, None, Some(&config_path), &option_args(&[]))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("line 1, column 10: Unknown preset \"sharp\""), "{}", err);
+        assert!(
+            err.contains("\"olm-like\", \"strict-lineart\", \"soft\", \"pixel-art\""),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn enum_names_match_config_values() {
+        let temp_dir = TempDir::new("config-enum-names");
+
+        for blend_space in BlendSpace::ALL {
+            let config_path = temp_dir.write("names.mlaa", format!("blend_space = \"{}\"\n", blend_space.name()));
+            let resolved_config = resolve_config(&temp_dir, None, Some(&config_path), &option_args(&[])).unwrap();
+            assert_eq!(resolved_config.options.blend_space, blend_space);
+
+            let resolved_config = resolve_config(
+                &temp_dir,
+                None,
+                None,
+                &option_args(&["--blend-space", blend_space.name()]),
+            )
+            .unwrap();
+            assert_eq!(resolved_config.options.blend_space, blend_space);
+        }
+
+        for brightness_metric in BrightnessMetric::ALL {
+            let config_path = temp_dir.write(
+                "names.mlaa",
+                format!("brightness_metric = \"{}\"\n", brightness_metric.name()),
+            );
+            let resolved_config = resolve_config(&temp_dir, None, Some(&config_path), &option_args(&[])).unwrap();
+            assert_eq!(resolved_config.options.brightness_metric, brightness_metric);
+
+            let resolved_config = resolve_config(
+                &temp_dir,
+                None,
+                None,
+                &option_args(&["--brightness-metric", brightness_metric.name()]),
+            )
+            .unwrap();
+            assert_eq!(resolved_config.options.brightness_metric, brightness_metric);
+        }
+    }
+
+    #[test]
+    fn unknown_enum_values_are_rejected_by_clap() {
+        let err = TestArgs::try_parse_from(["mlaa_image", "--blend-space", "hsv"])
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(
+            err.contains("[possible values: srgb, linear, oklab, cielab]"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn config_layers_override_each_other() {
+        let temp_dir = TempDir::new("config-layers");
+        let root_config_path = temp_dir.write(
+            ".mlaa",
+            "strict_mode = false\nblend_space = \"oklab\"\nseam_split_position = 0.25\n",
+        );
+        let directory_config_path = temp_dir.write("sub/.mlaa", "blend_space = \"cielab\"\ncorner_smoothing = false\n");
+        let explicit_config_path =
+            temp_dir.write("explicit.mlaa", "seam_split_position = 0.75\nblend_space = \"srgb\"\n");
+        let input_path = temp_dir.write("sub/image.png", "");
+
+        let resolved_config = resolve_config(
+            &temp_dir,
+            Some(&input_path),
+            Some(&explicit_config_path),
+            &option_args(&["--brightness-metric", "max_channel", "--blend-space", "linear"]),
+        )
+        .unwrap();
+
+        let options = &resolved_config.options;
+        assert!(!options.strict_mode);
+        assert!(!options.corner_smoothing);
+        assert_eq!(options.seam_split_position, 0.75);
+        assert_eq!(options.blend_space, BlendSpace::Linear);
+        assert_eq!(options.brightness_metric, BrightnessMetric::MaxChannel);
+
+        let source_path = |key: &str| {
+            resolved_config.value_sources[key]
+                .config_source
+                .path()
+                .map(Path::to_owned)
+        };
+        assert_eq!(source_path("strict_mode"), Some(root_config_path.clone()));
+        assert_eq!(source_path("corner_smoothing"), Some(directory_config_path.clone()));
+        assert_eq!(source_path("seam_split_position"), Some(explicit_config_path.clone()));
+        assert!(matches!(
+            resolved_config.value_sources["blend_space"].config_source,
+            ConfigSource::CommandLineFlags
+        ));
+
+        assert!(resolved_config.config_paths.ends_with(&[
+            root_config_path,
+            directory_config_path,
+            explicit_config_path
+        ]));
+    }
+
+    #[test]
+    fn config_sources_follow_the_given_environment() {
+        let temp_dir = TempDir::new("config-environment");
+        temp_dir.write(".mlaa", "");
+        let root_config_path = temp_dir.write("root/.mlaa", "");
+        let directory_config_path = temp_dir.write("root/sub/.mlaa", "");
+        let user_config_path = temp_dir.write("home/mlaa/config.toml", "");
+        let env_config_path = temp_dir.write("env.mlaa", "");
+        let input_path = temp_dir.write("root/sub/image.png", "");
+
+        let config_env = ConfigEnv {
+            config_home: Some(temp_dir.path().join("home")),
+            env_config_path: Some(env_config_path.clone()),
+            root: Some(temp_dir.path().join("root")),
+        };
+        let config_paths = config_sources(&config_env, Some(&input_path), None)
+            .iter()
+            .filter_map(|config_source| config_source.path().map(Path::to_owned))
+            .collect::<Vec<_>>();
+
+        // The config file above the root is left out.
+        assert_eq!(
+            config_paths,
+            [
+                user_config_path,
+                root_config_path,
+                directory_config_path,
+                env_config_path
+            ]
+        );
+    }
+
+    #[test]
+    fn rules_match_relative_paths() {
+        let temp_dir = TempDir::new("config-rules");
+        temp_dir.write(
+            ".mlaa",
+            r#"
+[[rules]]
+path = "*.png"
+strict_mode = false
+
+[[rules]]
+path = "**/lineart/*.png"
+preset = "strict-lineart"
+
+[[rules]]
+path = "sprites/*.png"
+blend_space = "srgb"
+
+[[rules]]
+path = "sprites/hero.png"
+blend_space = "oklab"
+"#,
+        );
+
+        let resolve = |relative_path: &str| {
+            resolve_config(
+                &temp_dir,
+                Some(&temp_dir.path().join(relative_path)),
+                None,
+                &option_args(&[]),
+            )
+            .unwrap()
+        };
+        let rule_pattern =
+            |resolved_config: &ResolvedConfig, key: &str| resolved_config.value_sources[key].rule_pattern.clone();
+
+        let resolved_config = resolve("image.png");
+        assert!(!resolved_config.options.strict_mode);
+        assert_eq!(rule_pattern(&resolved_config, "strict_mode").as_deref(), Some("*.png"));
+
+        // `*` doesn't match across directories.
+        let resolved_config = resolve("sub/image.png");
+        assert_eq!(rule_pattern(&resolved_config, "strict_mode"), None);
+
+        let resolved_config = resolve("a/b/lineart/image.png");
+        assert!(resolved_config.options == MlaaOptions::preset("strict-lineart").unwrap());
+        assert_eq!(
+            rule_pattern(&resolved_config, "seam_brigtness_balance").as_deref(),
+            Some("**/lineart/*.png")
+        );
+
+        // Later rules override earlier ones, with a warning about the conflict.
+        let resolved_config = resolve("sprites/hero.png");
+        assert_eq!(resolved_config.options.blend_space, BlendSpace::Oklab);
+        assert_eq!(resolved_config.warnings.len(), 1);
+        assert!(resolved_config.warnings[0].contains("Conflicting rules \"sprites/*.png\" and \"sprites/hero.png\""));
+
+        let resolved_config = resolve("sprites/villain.png");
+        assert_eq!(resolved_config.options.blend_space, BlendSpace::Srgb);
+        assert!(resolved_config.warnings.is_empty());
+    }
+}
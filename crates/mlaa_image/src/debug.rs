@@ -0,0 +1,383 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::{Args, ValueEnum};
+use image::{ImageFormat, RgbaImage};
+
+use mlaa_impl::{mlaa_dynamic_image, mlaa_dynamic_image_features, pixel_to_rgba, MlaaFeature, MlaaOptions};
+
+use crate::animation::is_animated;
+use crate::metadata::ImageMetadata;
+use crate::pipeline::{decode_image, encode_image};
+
+// Same visualization as in mlaa_egui, the sizes are given relative to its
+// 24 pixel wide cells.
+const EGUI_CELL_SIZE: f32 = 24.0;
+const BACKGROUND_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+const VERTICAL_COLOR: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+const HORIZONTAL_COLOR: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
+const CORNER_COLOR: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+// Larger debug images are refused instead of allocating gigabytes.
+const MAX_PIXEL_COUNT: u64 = 1 << 28;
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FeatureKind {
+    Vertical,
+    Horizontal,
+    Corner,
+}
+
+#[derive(Clone, Copy)]
+pub struct DebugRegion {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl FromStr for DebugRegion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid_region = || format!("invalid region \"{}\", expected X,Y,WIDTHxHEIGHT", s);
+
+        let parts = s
+            .split([',', 'x'])
+            .map(|part| part.trim().parse::<u32>().map_err(|_| invalid_region()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match parts[..] {
+            [x, y, width, height] if (width > 0) && (height > 0) => Ok(DebugRegion { x, y, width, height }),
+            _ => Err(invalid_region()),
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct DebugArgs {
+    /// Writes an upscaled copy of the processed image with the detected features drawn over it
+    #[clap(long = "debug-output")]
+    pub debug_output_path: Option<PathBuf>,
+
+    /// Feature kinds drawn into the debug output
+    #[clap(
+        long = "debug-features",
+        value_enum,
+        value_delimiter = ',',
+        default_values = ["vertical", "horizontal", "corner"]
+    )]
+    debug_features: Vec<FeatureKind>,
+
+    /// Size of one image pixel in the debug output
+    #[clap(long = "debug-scale", default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..=64))]
+    debug_scale: u32,
+
+    /// Only draws a part of the image, as X,Y,WIDTHxHEIGHT
+    #[clap(long = "debug-region")]
+    debug_region: Option<DebugRegion>,
+}
+
+// Source over compositing of a single pixel, coordinates outside of the image
+// are ignored.
+fn blend_pixel(image: &mut RgbaImage, x: i64, y: i64, c: [f32; 4]) {
+    if (x < 0) || (y < 0) || (x >= image.width() as i64) || (y >= image.height() as i64) {
+        return;
+    }
+
+    let pixel = image.get_pixel_mut(x as u32, y as u32);
+    let background = pixel.0.map(|v| v as f32 / 255.0);
+
+    for channel in 0..3 {
+        pixel[channel] = ((c[channel] * c[3] + background[channel] * (1.0 - c[3])) * 255.0).round() as u8;
+    }
+    pixel[3] = ((c[3] + background[3] * (1.0 - c[3])) * 255.0).round() as u8;
+}
+
+// Fills the pixels whose centers are inside the rectangle.
+fn fill_rect(image: &mut RgbaImage, (x0, y0): (f32, f32), (x1, y1): (f32, f32), c: [f32; 4]) {
+    for y in (y0 - 0.5).ceil() as i64..(y1 - 0.5).ceil() as i64 {
+        for x in (x0 - 0.5).ceil() as i64..(x1 - 0.5).ceil() as i64 {
+            blend_pixel(image, x, y, c);
+        }
+    }
+}
+
+// Strokes are centered on the edges of the rectangle, like in egui.
+fn stroke_rect(image: &mut RgbaImage, (x0, y0): (f32, f32), (x1, y1): (f32, f32), width: f32, c: [f32; 4]) {
+    let w = width / 2.0;
+
+    fill_rect(image, (x0 - w, y0 - w), (x1 + w, y0 + w), c);
+    fill_rect(image, (x0 - w, y1 - w), (x1 + w, y1 + w), c);
+    fill_rect(image, (x0 - w, y0 + w), (x0 + w, y1 - w), c);
+    fill_rect(image, (x1 - w, y0 + w), (x1 + w, y1 - w), c);
+}
+
+// Only horizontal and vertical lines are needed.
+fn draw_line(image: &mut RgbaImage, (x0, y0): (f32, f32), (x1, y1): (f32, f32), width: f32, c: [f32; 4]) {
+    let w = width / 2.0;
+    fill_rect(
+        image,
+        (x0.min(x1) - w, y0.min(y1) - w),
+        (x0.max(x1) + w, y0.max(y1) + w),
+        c,
+    );
+}
+
+fn draw_circle(
+    image: &mut RgbaImage,
+    (cx, cy): (f32, f32),
+    radius: f32,
+    fill_color: [f32; 4],
+    stroke_width: f32,
+    stroke_color: [f32; 4],
+) {
+    let outer_radius = radius + stroke_width / 2.0;
+
+    for y in (cy - outer_radius).floor() as i64..=(cy + outer_radius).ceil() as i64 {
+        for x in (cx - outer_radius).floor() as i64..=(cx + outer_radius).ceil() as i64 {
+            let distance = (x as f32 + 0.5 - cx).hypot(y as f32 + 0.5 - cy);
+
+            if distance <= radius - stroke_width / 2.0 {
+                blend_pixel(image, x, y, fill_color);
+            } else if distance <= outer_radius {
+                blend_pixel(image, x, y, stroke_color);
+            }
+        }
+    }
+}
+
+fn draw_feature(
+    image: &mut RgbaImage,
+    mlaa_feature: &MlaaFeature<[f32; 4]>,
+    (origin_x, origin_y): (f32, f32),
+    scale: f32,
+    args: &DebugArgs,
+) {
+    let point = |x: f32, y: f32| ((x - origin_x) * scale, (y - origin_y) * scale);
+    let size = |egui_size: f32| (egui_size * scale / EGUI_CELL_SIZE).max(1.0);
+
+    let (stroke_thin, stroke_bold) = (size(2.0), size(3.0));
+    let (dot_radius, large_dot_radius) = (size(4.0), size(8.0));
+
+    match *mlaa_feature {
+        MlaaFeature::VerticalGradient { x, y, height, colors } => {
+            if args.debug_features.contains(&FeatureKind::Vertical) {
+                let (top, bottom) = (point(x + 0.5, y), point(x + 0.5, y + height));
+
+                stroke_rect(
+                    image,
+                    point(x, y),
+                    point(x + 1.0, y + height),
+                    stroke_thin,
+                    VERTICAL_COLOR,
+                );
+                draw_line(image, top, bottom, stroke_bold, VERTICAL_COLOR);
+                draw_circle(image, top, dot_radius, colors.0, stroke_thin, VERTICAL_COLOR);
+                draw_circle(image, bottom, dot_radius, colors.1, stroke_thin, VERTICAL_COLOR);
+            }
+        }
+        MlaaFeature::HorizontalGradient { x, y, width, colors } => {
+            if args.debug_features.contains(&FeatureKind::Horizontal) {
+                let (left, right) = (point(x, y + 0.5), point(x + width, y + 0.5));
+
+                stroke_rect(
+                    image,
+                    point(x, y),
+                    point(x + width, y + 1.0),
+                    stroke_thin,
+                    HORIZONTAL_COLOR,
+                );
+                draw_line(image, left, right, stroke_bold, HORIZONTAL_COLOR);
+                draw_circle(image, left, dot_radius, colors.0, stroke_thin, HORIZONTAL_COLOR);
+                draw_circle(image, right, dot_radius, colors.1, stroke_thin, HORIZONTAL_COLOR);
+            }
+        }
+        MlaaFeature::Corner { x, y, colors } => {
+            if args.debug_features.contains(&FeatureKind::Corner) {
+                let (x, y) = (x as f32, y as f32);
+                let center = point(x + 0.5, y + 0.5);
+
+                stroke_rect(image, point(x, y), point(x + 1.0, y + 1.0), stroke_thin, CORNER_COLOR);
+                draw_circle(image, center, large_dot_radius, colors.0, stroke_thin, CORNER_COLOR);
+                draw_circle(image, center, dot_radius, colors.1, stroke_thin, CORNER_COLOR);
+            }
+        }
+    }
+}
+
+pub fn encode_debug_image(
+    input_data: &[u8],
+    input_format: ImageFormat,
+    mlaa_options: &MlaaOptions,
+    args: &DebugArgs,
+    debug_format: ImageFormat,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    if is_animated(input_data, input_format)? {
+        return Err("--debug-output doesn't support animated images".into());
+    }
+
+    let input_image = decode_image(input_data, input_format)?;
+    let (width, height) = (input_image.width(), input_image.height());
+
+    let region = args.debug_region.unwrap_or(DebugRegion {
+        x: 0,
+        y: 0,
+        width,
+        height,
+    });
+
+    if (region.x >= width) || (region.y >= height) {
+        return Err(format!("Debug region is outside of the {}x{} image", width, height).into());
+    }
+    let region_width = region.width.min(width - region.x);
+    let region_height = region.height.min(height - region.y);
+
+    let scale = args.debug_scale;
+    let (debug_width, debug_height) = (region_width * scale, region_height * scale);
+    if debug_width as u64 * debug_height as u64 > MAX_PIXEL_COUNT {
+        return Err(format!(
+            "Debug output would be {}x{} pixels, use --debug-region or a smaller --debug-scale",
+            debug_width, debug_height
+        )
+        .into());
+    }
+
+    // Float images are processed as linear light, the same way as the output
+    // image.
+    let features = mlaa_dynamic_image_features(&input_image, mlaa_options);
+    let output_image = mlaa_dynamic_image(&input_image, mlaa_options).into_rgba32f();
+
+    let mut debug_image = RgbaImage::new(debug_width, debug_height);
+    fill_rect(
+        &mut debug_image,
+        (0.0, 0.0),
+        (debug_width as f32, debug_height as f32),
+        BACKGROUND_COLOR,
+    );
+
+    // The pixels are drawn as separate cells once they are large enough.
+    let cell_gap = if scale >= 4 { 1.0 } else { 0.0 };
+
+    for y in 0..region_height {
+        for x in 0..region_width {
+            let c = pixel_to_rgba(output_image.get_pixel(region.x + x, region.y + y));
+            let (cell_x, cell_y) = ((x * scale) as f32, (y * scale) as f32);
+
+            fill_rect(
+                &mut debug_image,
+                (cell_x + cell_gap, cell_y + cell_gap),
+                (cell_x + scale as f32 - cell_gap, cell_y + scale as f32 - cell_gap),
+                c,
+            );
+        }
+    }
+
+    for mlaa_feature in &features {
+        draw_feature(
+            &mut debug_image,
+            mlaa_feature,
+            (region.x as f32, region.y as f32),
+            scale as f32,
+            args,
+        );
+    }
+
+    encode_image(debug_image.into(), debug_format, &ImageMetadata::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{DynamicImage, Rgba, Rgba32FImage};
+    use mlaa_impl::{mlaa_image_buffer, mlaa_linear_image_buffer};
+
+    use super::*;
+
+    const SCALE: u32 = 8;
+
+    fn debug_args(debug_features: &[FeatureKind]) -> DebugArgs {
+        DebugArgs {
+            debug_output_path: None,
+            debug_features: debug_features.to_vec(),
+            debug_scale: SCALE,
+            debug_region: None,
+        }
+    }
+
+    fn staircase(white: f32) -> Rgba32FImage {
+        Rgba32FImage::from_fn(8, 8, |x, y| {
+            let v = if x + y < 8 { 0.0 } else { white };
+            Rgba([v, v, v, 1.0])
+        })
+    }
+
+    fn debug_image(input_image: DynamicImage, input_format: ImageFormat, args: &DebugArgs) -> RgbaImage {
+        let mut input_data = Vec::new();
+        input_image
+            .write_to(&mut Cursor::new(&mut input_data), input_format)
+            .unwrap();
+
+        let debug_data = encode_debug_image(
+            &input_data,
+            input_format,
+            &MlaaOptions::default(),
+            args,
+            ImageFormat::Png,
+        )
+        .unwrap();
+        image::load_from_memory(&debug_data).unwrap().into_rgba8()
+    }
+
+    fn contains_color(image: &RgbaImage, c: [f32; 4]) -> bool {
+        image.pixels().any(|pixel| pixel_to_rgba(pixel) == c)
+    }
+
+    #[test]
+    fn feature_kinds_have_their_colors() {
+        // A staircase with gradients of both directions next to a box with
+        // corners
+        let input_image = RgbaImage::from_fn(16, 8, |x, y| {
+            let is_dark = if x < 8 {
+                x + y < 8
+            } else {
+                (10..14).contains(&x) && (2..6).contains(&y)
+            };
+            let v = if is_dark { 0 } else { 255 };
+            Rgba([v, v, v, 255])
+        });
+        let kinds = [
+            (FeatureKind::Vertical, VERTICAL_COLOR),
+            (FeatureKind::Horizontal, HORIZONTAL_COLOR),
+            (FeatureKind::Corner, CORNER_COLOR),
+        ];
+
+        let image = debug_image(input_image.clone().into(), ImageFormat::Png, &debug_args(&[]));
+        assert!(kinds.iter().all(|&(_, c)| !contains_color(&image, c)));
+
+        for (kind, kind_color) in kinds {
+            let image = debug_image(input_image.clone().into(), ImageFormat::Png, &debug_args(&[kind]));
+
+            for (other_kind, c) in kinds {
+                assert_eq!(contains_color(&image, c), other_kind == kind);
+            }
+            assert!(contains_color(&image, kind_color));
+        }
+    }
+
+    #[test]
+    fn float_images_are_linear() {
+        let input_image = staircase(1.0);
+        let output_image = mlaa_linear_image_buffer(&input_image, &MlaaOptions::default());
+        assert!(output_image != mlaa_image_buffer(&input_image, &MlaaOptions::default()));
+
+        let image = debug_image(input_image.into(), ImageFormat::OpenExr, &debug_args(&[]));
+
+        for (x, y, pixel) in output_image.enumerate_pixels() {
+            let cell_pixel = image.get_pixel(x * SCALE + SCALE / 2, y * SCALE + SCALE / 2);
+            assert_eq!(*cell_pixel, Rgba(pixel.0.map(|v| (v * 255.0).round() as u8)));
+        }
+    }
+}
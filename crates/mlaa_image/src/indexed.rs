@@ -0,0 +1,317 @@
+use std::error::Error;
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageBuffer, Rgb, Rgba};
+use png::{BitDepth, ColorType, Transformations};
+
+use mlaa_impl::{mlaa_features, mlaa_painter, rgba_to_pixel, Channel, MlaaOptions};
+
+use crate::metadata::ImageMetadata;
+
+pub struct IndexedImage {
+    pub width: u32,
+    pub height: u32,
+    pub indices: Vec<u8>,
+    pub palette: Vec<[u8; 4]>,
+}
+
+impl IndexedImage {
+    // Returns `None` for PNG files not using an indexed color type.
+    pub fn from_png(png_data: &[u8]) -> Result<Option<IndexedImage>, Box<dyn Error>> {
+        let mut decoder = png::Decoder::new(Cursor::new(png_data));
+        decoder.set_transformations(Transformations::IDENTITY);
+
+        let mut reader = decoder.read_info()?;
+        if reader.info().color_type != ColorType::Indexed {
+            return Ok(None);
+        }
+
+        let palette = {
+            let info = reader.info();
+            let palette_rgb = info.palette.as_deref().ok_or("Indexed PNG has no palette")?;
+            let palette_alpha = info.trns.as_deref().unwrap_or(&[]);
+
+            palette_rgb
+                .chunks_exact(3)
+                .enumerate()
+                .map(|(index, rgb)| [rgb[0], rgb[1], rgb[2], palette_alpha.get(index).copied().unwrap_or(255)])
+                .collect::<Vec<_>>()
+        };
+
+        let mut frame_data = vec![0; reader.output_buffer_size()];
+        let frame_info = reader.next_frame(&mut frame_data)?;
+
+        let bits_per_index = frame_info.bit_depth as usize;
+        let indices_per_byte = 8 / bits_per_index;
+        let index_mask = ((1u16 << bits_per_index) - 1) as u8;
+
+        let mut indices = Vec::with_capacity(frame_info.width as usize * frame_info.height as usize);
+        for line in frame_data.chunks_exact(frame_info.line_size) {
+            for x in 0..frame_info.width as usize {
+                let shift = 8 - bits_per_index * (x % indices_per_byte + 1);
+                indices.push((line[x / indices_per_byte] >> shift) & index_mask);
+            }
+        }
+
+        Ok(Some(IndexedImage {
+            width: frame_info.width,
+            height: frame_info.height,
+            indices,
+            palette,
+        }))
+    }
+
+    pub fn to_png(&self, metadata: &ImageMetadata) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut png_data = Vec::new();
+
+        {
+            let mut encoder = metadata.png_encoder(&mut png_data, self.width, self.height)?;
+            encoder.set_color(ColorType::Indexed);
+            encoder.set_depth(BitDepth::Eight);
+            encoder.set_palette(self.palette.iter().flat_map(|c| [c[0], c[1], c[2]]).collect::<Vec<_>>());
+
+            if let Some(last_translucent) = self.palette.iter().rposition(|c| c[3] != 255) {
+                encoder.set_trns(
+                    self.palette[..=last_translucent]
+                        .iter()
+                        .map(|c| c[3])
+                        .collect::<Vec<_>>(),
+                );
+            }
+
+            let mut writer = encoder.write_header()?;
+            writer.write_image_data(&self.indices)?;
+        }
+
+        Ok(png_data)
+    }
+
+    fn color(&self, index: Option<u8>) -> [f32; 4] {
+        index
+            .and_then(|index| self.palette.get(index as usize))
+            .map(|c| c.map(Channel::to_unit))
+            .unwrap_or([0.0; 4])
+    }
+
+    fn has_translucency(&self) -> bool {
+        self.palette.iter().any(|c| c[3] != 255)
+    }
+}
+
+fn mlaa_indexed_painter(
+    input_image: &IndexedImage,
+    mlaa_options: &MlaaOptions,
+    mut draw_pixel: impl FnMut(u32, u32, [f32; 4]),
+) {
+    // Features are detected on the palette indices, the palette colors are
+    // only looked up for the brightness comparisons and blending.
+    mlaa_features(
+        input_image.width as usize,
+        input_image.height as usize,
+        |x, y| {
+            if (x < 0) || (y < 0) || (x >= input_image.width as isize) || (y >= input_image.height as isize) {
+                None
+            } else {
+                Some(input_image.indices[y as usize * input_image.width as usize + x as usize])
+            }
+        },
+        |c| mlaa_options.brightness_metric.brightness(input_image.color(c)),
+        mlaa_options,
+        |mlaa_feature| {
+            mlaa_painter(
+                |c1, c2, t| {
+                    mlaa_options
+                        .blend_space
+                        .blend(input_image.color(c1), input_image.color(c2), t)
+                },
+                |x, y, c| draw_pixel(x as u32, y as u32, c),
+                &mlaa_feature,
+            );
+        },
+    );
+}
+
+pub fn mlaa_process_indexed(input_image: &IndexedImage, mlaa_options: &MlaaOptions) -> DynamicImage {
+    let pixels = input_image.indices.iter().map(|&index| input_image.color(Some(index)));
+
+    if input_image.has_translucency() {
+        let mut output_image = ImageBuffer::<Rgba<u8>, _>::from_vec(
+            input_image.width,
+            input_image.height,
+            pixels.flat_map(|c| c.map(u8::from_unit)).collect(),
+        )
+        .unwrap();
+
+        mlaa_indexed_painter(input_image, mlaa_options, |x, y, c| {
+            output_image.put_pixel(x, y, rgba_to_pixel(c));
+        });
+
+        output_image.into()
+    } else {
+        let mut output_image = ImageBuffer::<Rgb<u8>, _>::from_vec(
+            input_image.width,
+            input_image.height,
+            pixels.flat_map(|c| [c[0], c[1], c[2]].map(u8::from_unit)).collect(),
+        )
+        .unwrap();
+
+        mlaa_indexed_painter(input_image, mlaa_options, |x, y, c| {
+            output_image.put_pixel(x, y, rgba_to_pixel(c));
+        });
+
+        output_image.into()
+    }
+}
+
+// Returns `None` when the blended colors don't fit into the palette.
+pub fn mlaa_process_indexed_palette(input_image: &IndexedImage, mlaa_options: &MlaaOptions) -> Option<IndexedImage> {
+    let mut output_image = IndexedImage {
+        width: input_image.width,
+        height: input_image.height,
+        indices: input_image.indices.clone(),
+        palette: input_image.palette.clone(),
+    };
+
+    let mut palette_overflow = false;
+
+    mlaa_indexed_painter(input_image, mlaa_options, |x, y, c| {
+        let c = c.map(u8::from_unit);
+
+        let palette_index = if let Some(palette_index) = output_image.palette.iter().position(|&p| p == c) {
+            palette_index
+        } else if output_image.palette.len() < 256 {
+            output_image.palette.push(c);
+            output_image.palette.len() - 1
+        } else {
+            palette_overflow = true;
+            return;
+        };
+
+        output_image.indices[(y * output_image.width + x) as usize] = palette_index as u8;
+    });
+
+    (!palette_overflow).then_some(output_image)
+}
+
+#[derive(Default)]
+pub struct QuantizationError {
+    pub pixel_count: usize,
+    pub total_error: f32,
+    pub max_error: f32,
+}
+
+impl QuantizationError {
+    pub fn add(&mut self, error: f32) {
+        self.pixel_count += 1;
+        self.total_error += error;
+        self.max_error = self.max_error.max(error);
+    }
+
+    pub fn mean_error(&self) -> f32 {
+        if self.pixel_count == 0 {
+            0.0
+        } else {
+            self.total_error / self.pixel_count as f32
+        }
+    }
+}
+
+// Distance of two colors in 8-bit units. The colors are compared
+// premultiplied, so every fully transparent color is the same.
+fn color_distance(c1: [f32; 4], c2: [f32; 4]) -> f32 {
+    let premultiply = |[r, g, b, a]: [f32; 4]| [r * a, g * a, b * a, a];
+    let (c1, c2) = (premultiply(c1), premultiply(c2));
+
+    (0..4)
+        .map(|channel| (c1[channel] - c2[channel]).powi(2))
+        .sum::<f32>()
+        .sqrt()
+        * u8::MAX as f32
+}
+
+// Returns the index of the palette color closest to the color, and its
+// distance.
+pub fn closest_palette_color(palette: &[[u8; 4]], c: [f32; 4]) -> Option<(u8, f32)> {
+    palette
+        .iter()
+        .enumerate()
+        .map(|(index, p)| (index as u8, color_distance(c, p.map(Channel::to_unit))))
+        .min_by(|(_, d1), (_, d2)| d1.total_cmp(d2))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::RgbaImage;
+    use mlaa_impl::mlaa_dynamic_image;
+
+    use super::*;
+
+    // Palette index 0 on the upper left and 1 on the lower right half.
+    fn staircase(palette: Vec<[u8; 4]>) -> IndexedImage {
+        IndexedImage {
+            width: 8,
+            height: 8,
+            indices: (0..8 * 8).map(|i| u8::from((i % 8) + (i / 8) >= 8)).collect(),
+            palette,
+        }
+    }
+
+    fn expanded(image: &IndexedImage) -> RgbaImage {
+        RgbaImage::from_fn(image.width, image.height, |x, y| {
+            Rgba(image.palette[image.indices[(y * image.width + x) as usize] as usize])
+        })
+    }
+
+    #[test]
+    fn features_match_the_expanded_image() {
+        let mlaa_options = MlaaOptions::default();
+
+        let opaque = staircase(vec![[20, 40, 60, 255], [240, 220, 200, 255]]);
+        assert!(
+            mlaa_process_indexed(&opaque, &mlaa_options)
+                == mlaa_dynamic_image(&DynamicImage::from(expanded(&opaque)).into_rgb8().into(), &mlaa_options)
+        );
+
+        let translucent = staircase(vec![[20, 40, 60, 255], [240, 220, 200, 100]]);
+        assert!(
+            mlaa_process_indexed(&translucent, &mlaa_options)
+                == mlaa_dynamic_image(&expanded(&translucent).into(), &mlaa_options)
+        );
+    }
+
+    #[test]
+    fn blended_colors_are_appended_to_the_palette() {
+        let input_image = staircase(vec![[0, 0, 0, 255], [255, 255, 255, 255]]);
+        let mlaa_options = MlaaOptions::default();
+
+        let output_image = mlaa_process_indexed_palette(&input_image, &mlaa_options).unwrap();
+        assert!((output_image.palette.len() > 2) && (output_image.palette.len() <= 256));
+        assert_eq!(output_image.palette[..2], input_image.palette);
+        assert!(
+            DynamicImage::from(expanded(&output_image)).into_rgb8()
+                == mlaa_process_indexed(&input_image, &mlaa_options).into_rgb8()
+        );
+    }
+
+    #[test]
+    fn full_palettes_overflow() {
+        // Colorful entries, none of them is a blend of black and white.
+        let mut palette = vec![[0, 0, 0, 255], [255, 255, 255, 255]];
+        palette.extend((0..254).map(|i| [i as u8, 0, 255 - i as u8, 255]));
+
+        assert!(mlaa_process_indexed_palette(&staircase(palette), &MlaaOptions::default()).is_none());
+    }
+
+    #[test]
+    fn transparency_is_written_to_trns() {
+        let image = staircase(vec![[0, 0, 0, 128], [255, 255, 255, 255], [10, 20, 30, 0]]);
+        let png_data = image.to_png(&ImageMetadata::default()).unwrap();
+
+        let reader = png::Decoder::new(Cursor::new(&png_data)).read_info().unwrap();
+        assert_eq!(reader.info().trns.as_deref(), Some(&[128, 255, 0][..]));
+
+        let decoded_image = IndexedImage::from_png(&png_data).unwrap().unwrap();
+        assert_eq!(decoded_image.palette, image.palette);
+        assert_eq!(decoded_image.indices, image.indices);
+    }
+}
@@ -2,26 +2,205 @@
 
 use std::error::Error;
 use std::fs::{self, File};
-use std::io::{Cursor, Read, Write};
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 
-use clap::Parser;
-use image::{ImageFormat, Pixel, Rgba};
+use clap::builder::{EnumValueParser, TypedValueParser};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use glob::Pattern;
+use image::ImageFormat;
 
-use mlaa_impl::{mlaa_features, mlaa_painter, MlaaOptions};
+use crate::batch::{batch_command, is_batch};
+use crate::config::{config_schema, resolve_config, OptionArgs};
+use crate::debug::{encode_debug_image, DebugArgs};
+use crate::metadata::ImageMetadata;
+use crate::ora::{check_ora_output, is_ora, ora_layer_jobs, process_ora};
+use crate::overlay::encode_overlay;
+use crate::pipeline::{detect_image_format, output_metadata, process_image};
+use crate::provenance::Provenance;
+use crate::stream::{stream_command, StreamArgs};
+use crate::watch::watch_command;
+
+mod animation;
+mod batch;
+mod cache;
+mod config;
+mod debug;
+mod indexed;
+mod metadata;
+mod ora;
+mod overlay;
+mod pam;
+mod pipeline;
+mod provenance;
+mod stream;
+#[cfg(test)]
+mod test_util;
+mod watch;
 
 #[derive(Parser)]
-#[command(version)]
+#[command(version, args_conflicts_with_subcommands = true)]
 struct MlaaArgs {
+    #[command(subcommand)]
+    command: Option<MlaaCommand>,
+
+    #[command(flatten)]
+    process_args: ProcessArgs,
+}
+
+#[derive(Subcommand)]
+enum MlaaCommand {
+    /// Prints the provenance record embedded into a processed image
+    Info { path: PathBuf },
+
+    /// Shows the effective MLAA options for an input file or directory
+    Config(ConfigArgs),
+
+    /// Watches a directory and reprocesses the images and configs changed in it
+    Watch {
+        directory: PathBuf,
+
+        #[command(flatten)]
+        process_args: Box<ProcessArgs>,
+    },
+
+    /// Processes raw video frames from stdin to stdout, for use in ffmpeg pipelines
+    Stream(StreamArgs),
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("action").required(true).args(["explain", "dump", "schema"])))]
+struct ConfigArgs {
+    /// Input file or directory the config is resolved for, defaults to the current directory
+    path: Option<PathBuf>,
+
+    #[clap(short = 'c', long = "config")]
+    config_path: Option<PathBuf>,
+
+    #[command(flatten)]
+    option_args: OptionArgs,
+
+    /// Prints every option along with the config file it was set in
+    #[clap(long = "explain")]
+    explain: bool,
+
+    /// Prints the effective options as TOML
+    #[clap(long = "dump")]
+    dump: bool,
+
+    /// Prints the JSON Schema of the config files
+    #[clap(long = "schema")]
+    schema: bool,
+}
+
+#[derive(Args)]
+struct ProcessArgs {
+    /// Input file, directory or glob pattern, can be given multiple times
     #[clap(short = 'i', long = "input")]
-    input_path: Option<PathBuf>,
+    input_paths: Vec<PathBuf>,
 
     #[clap(short = 'o', long = "output")]
     output_path: Option<PathBuf>,
 
+    /// Also writes the pixels changed by the antialiasing on a transparent background, for compositing over the input image
+    #[clap(long = "output-overlay")]
+    output_overlay_path: Option<PathBuf>,
+
+    /// Output directory for batch processing
+    #[clap(long = "output-dir")]
+    output_dir: Option<PathBuf>,
+
+    /// Output path template for batch processing, supports the {dir}, {stem} and {ext} placeholders
+    #[clap(long = "output-template")]
+    output_template: Option<String>,
+
+    /// Also process the subdirectories of the input directories
+    #[clap(short = 'r', long = "recursive")]
+    recursive: bool,
+
+    /// Number of files processed in parallel
+    #[clap(short = 'j', long = "jobs")]
+    jobs: Option<usize>,
+
+    /// Cache file for skipping unchanged files in batch runs
+    #[clap(long = "cache")]
+    cache_path: Option<PathBuf>,
+
+    /// Process every file, even if the cache says it is up to date
+    #[clap(long = "force")]
+    force: bool,
+
     #[clap(short = 'c', long = "config")]
     config_path: Option<PathBuf>,
+
+    #[command(flatten)]
+    option_args: OptionArgs,
+
+    /// Overrides the input format detected from the file contents and extension
+    #[clap(long = "input-format", value_parser = EnumValueParser::<FormatName>::new().map(ImageFormat::from))]
+    input_format: Option<ImageFormat>,
+
+    /// Overrides the output format chosen from the output file extension
+    #[clap(long = "output-format", value_parser = EnumValueParser::<FormatName>::new().map(ImageFormat::from))]
+    output_format: Option<ImageFormat>,
+
+    /// Writes indexed PNG input as indexed PNG, adding the blended colors to the palette while it has room
+    #[clap(long = "keep-palette")]
+    keep_palette: bool,
+
+    /// Doesn't copy the metadata of the input image, only the provenance record is written
+    #[clap(long = "strip-metadata")]
+    strip_metadata: bool,
+
+    /// Processes images already processed by mlaa_image again, replacing their provenance record
+    #[clap(long = "reprocess")]
+    reprocess: bool,
+
+    #[command(flatten)]
+    debug_args: DebugArgs,
+
+    /// Only process the ORA layers whose name or path matches the glob pattern, can be given multiple times
+    #[clap(long = "layers")]
+    layer_patterns: Vec<Pattern>,
+
+    /// Adds the antialiasing of ORA layers as separate overlay layers instead of changing the layers
+    #[clap(long = "ora-overlay")]
+    ora_overlay: bool,
+}
+
+// Image formats enabled in the `image` dependency, clap lists them in the
+// help and in the errors.
+#[derive(Clone, Copy, ValueEnum)]
+enum FormatName {
+    Png,
+    Gif,
+    Bmp,
+    Tga,
+    #[value(alias = "tif")]
+    Tiff,
+    Webp,
+    Qoi,
+    #[value(aliases = ["pbm", "pgm", "ppm", "pam"])]
+    Pnm,
+    #[value(alias = "openexr")]
+    Exr,
+}
+
+impl From<FormatName> for ImageFormat {
+    fn from(format_name: FormatName) -> ImageFormat {
+        match format_name {
+            FormatName::Png => ImageFormat::Png,
+            FormatName::Gif => ImageFormat::Gif,
+            FormatName::Bmp => ImageFormat::Bmp,
+            FormatName::Tga => ImageFormat::Tga,
+            FormatName::Tiff => ImageFormat::Tiff,
+            FormatName::Webp => ImageFormat::WebP,
+            FormatName::Qoi => ImageFormat::Qoi,
+            FormatName::Pnm => ImageFormat::Pnm,
+            FormatName::Exr => ImageFormat::OpenExr,
+        }
+    }
 }
 
 // cargo run --release --bin mlaa_image -- -i test/input.png -o test/output.png
@@ -41,98 +220,161 @@ fn main() -> ExitCode {
 fn main_inner() -> Result<ExitCode, Box<dyn Error>> {
     let args = MlaaArgs::try_parse()?;
 
-    let mlaa_options = {
-        let config_path = {
-            if let Some(config_path) = args.config_path {
-                Some(config_path)
-            } else if let Some(search_root) = args.input_path.as_ref().and_then(|path| path.parent()) {
-                search_root
-                    .ancestors()
-                    .map(|search_directory| search_directory.to_owned().join(".mlaa"))
-                    .find(|config_path| config_path.is_file())
-            } else {
-                None
-            }
-        };
+    match args.command {
+        Some(MlaaCommand::Info { path }) => info_command(&path),
+        Some(MlaaCommand::Config(config_args)) => config_command(config_args),
+        Some(MlaaCommand::Watch {
+            directory,
+            process_args,
+        }) => watch_command(&directory, *process_args),
+        Some(MlaaCommand::Stream(stream_args)) => stream_command(stream_args),
+        None => process_command(args.process_args),
+    }
+}
 
-        if let Some(config_path) = config_path {
-            eprintln!("mlaa_image: Using config file \"{}\"", config_path.display());
-            toml::from_str(&fs::read_to_string(config_path)?)?
-        } else {
-            eprintln!("mlaa_image: Using default MLAA options");
-            MlaaOptions::default()
+fn info_command(path: &Path) -> Result<ExitCode, Box<dyn Error>> {
+    let image_data = fs::read(path)?;
+    let image_format = detect_image_format(&image_data, None, Some(path))?;
+
+    if let Some(provenance) = Provenance::find(&ImageMetadata::read(&image_data, image_format)?)? {
+        print!("{}", provenance.to_toml()?);
+        Ok(ExitCode::SUCCESS)
+    } else {
+        eprintln!("mlaa_image: No provenance record found in \"{}\"", path.display());
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+fn config_command(args: ConfigArgs) -> Result<ExitCode, Box<dyn Error>> {
+    if args.schema {
+        println!("{}", serde_json::to_string_pretty(&config_schema()?)?);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let path = args.path.unwrap_or_else(|| PathBuf::from("."));
+    let resolved_config = resolve_config(Some(&path), args.config_path.as_deref(), &args.option_args)?;
+    for warning in &resolved_config.warnings {
+        eprintln!("mlaa_image: {}", warning);
+    }
+
+    if args.explain {
+        let lines = resolved_config
+            .values
+            .iter()
+            .map(|(key, value)| (format!("{} = {}", key, value), &resolved_config.value_sources[key]))
+            .collect::<Vec<_>>();
+
+        let width = lines.iter().map(|(line, _)| line.len()).max().unwrap_or(0);
+        for (line, config_source) in lines {
+            println!("{:width$}  # {}", line, config_source, width = width);
         }
-    };
+    }
+
+    if args.dump {
+        print!("{}", toml::to_string(&resolved_config.options)?);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn process_command(args: ProcessArgs) -> Result<ExitCode, Box<dyn Error>> {
+    if is_batch(&args) {
+        return batch_command(&args);
+    }
 
-    let input_image = {
-        let mut reader: Box<dyn Read> = if let Some(input_path) = args.input_path.as_ref() {
+    if args.cache_path.is_some() {
+        return Err("--cache only works when processing multiple files".into());
+    }
+
+    let input_path = args.input_paths.first().map(PathBuf::as_path);
+
+    let resolved_config = resolve_config(input_path, args.config_path.as_deref(), &args.option_args)?;
+    if resolved_config.config_paths.is_empty() {
+        eprintln!("mlaa_image: Using default MLAA options");
+    }
+    for config_path in &resolved_config.config_paths {
+        eprintln!("mlaa_image: Using config file \"{}\"", config_path.display());
+    }
+    for warning in &resolved_config.warnings {
+        eprintln!("mlaa_image: {}", warning);
+    }
+
+    let input_data = {
+        let mut reader: Box<dyn Read> = if let Some(input_path) = input_path {
             Box::new(File::open(input_path)?)
         } else {
             Box::new(std::io::stdin())
         };
 
-        let image_format = if let Some(input_path) = args.input_path.as_ref() {
-            ImageFormat::from_path(input_path)?
+        let mut image_data = Vec::new();
+        reader.read_to_end(&mut image_data)?;
+        image_data
+    };
+
+    let output_data = if is_ora(&input_data) {
+        check_ora_output(args.output_path.as_deref(), &args)?;
+        if args.output_overlay_path.is_some() {
+            return Err("--output-overlay doesn't support ORA input, use --ora-overlay".into());
+        }
+        if args.debug_args.debug_output_path.is_some() {
+            return Err("--debug-output doesn't support ORA input".into());
+        }
+
+        let layer_jobs = ora_layer_jobs(&input_data, input_path, &resolved_config, &args)?;
+        process_ora(&input_data, &layer_jobs, &args)?
+    } else {
+        let input_format = detect_image_format(&input_data, args.input_format, input_path)?;
+
+        let output_format = if let Some(output_format) = args.output_format {
+            output_format
+        } else if let Some(output_path) = args.output_path.as_ref() {
+            ImageFormat::from_path(output_path)?
         } else {
             ImageFormat::Png
         };
 
-        let mut image_data = Vec::new();
-        reader.read_to_end(&mut image_data)?;
+        if let Some(debug_output_path) = args.debug_args.debug_output_path.as_ref() {
+            let debug_data = encode_debug_image(
+                &input_data,
+                input_format,
+                &resolved_config.options,
+                &args.debug_args,
+                ImageFormat::from_path(debug_output_path)?,
+            )?;
+            fs::write(debug_output_path, debug_data)?;
+        }
 
-        image::load_from_memory_with_format(&image_data, image_format)?
-    };
+        let output_data = process_image(
+            &input_data,
+            input_format,
+            output_format,
+            &resolved_config.options,
+            resolved_config.config_paths.clone(),
+            &args,
+        )?;
 
-    let input_image = input_image.to_rgba8();
-    let mut output_image = input_image.clone();
-
-    mlaa_features(
-        input_image.width() as usize,
-        input_image.height() as usize,
-        |x, y| {
-            input_image
-                .get_pixel_checked(x as u32, y as u32)
-                .unwrap_or(&Rgba([0, 0, 0, 0]))
-                .to_owned()
-        },
-        |c| c.to_luma().0,
-        &mlaa_options,
-        |mlaa_feature| {
-            mlaa_painter(
-                |c1, c2, t| {
-                    // The `image` crate doesn't give a fuck about gamma correctness.
-                    // Have to use `smol-rgb` instead of their blending functions.
-
-                    fn lerp(a: f32, b: f32, t: f32) -> f32 {
-                        a * (1.0 - t) + b * t
-                    }
-
-                    Rgba([
-                        smol_rgb::linear_to_encoded(lerp(
-                            smol_rgb::encoded_to_linear(c1.0[0]),
-                            smol_rgb::encoded_to_linear(c2.0[0]),
-                            t,
-                        )),
-                        smol_rgb::linear_to_encoded(lerp(
-                            smol_rgb::encoded_to_linear(c1.0[1]),
-                            smol_rgb::encoded_to_linear(c2.0[1]),
-                            t,
-                        )),
-                        smol_rgb::linear_to_encoded(lerp(
-                            smol_rgb::encoded_to_linear(c1.0[2]),
-                            smol_rgb::encoded_to_linear(c2.0[2]),
-                            t,
-                        )),
-                        lerp(c1.0[3] as f32, c2.0[3] as f32, t) as u8,
-                    ])
-                },
-                |x, y, c| {
-                    output_image.put_pixel(x as u32, y as u32, c);
-                },
-                &mlaa_feature,
-            );
-        },
-    );
+        if let Some(output_overlay_path) = args.output_overlay_path.as_ref() {
+            let metadata = output_metadata(
+                &input_data,
+                input_format,
+                &resolved_config.options,
+                resolved_config.config_paths,
+                &args,
+            )?;
+
+            let overlay_data = encode_overlay(
+                &input_data,
+                input_format,
+                &output_data,
+                output_format,
+                ImageFormat::from_path(output_overlay_path)?,
+                &metadata,
+            )?;
+            fs::write(output_overlay_path, overlay_data)?;
+        }
+
+        output_data
+    };
 
     {
         let mut writer: Box<dyn Write> = if let Some(output_path) = args.output_path.as_ref() {
@@ -141,17 +383,45 @@ fn main_inner() -> Result<ExitCode, Box<dyn Error>> {
             Box::new(std::io::stdout())
         };
 
-        let image_format = if let Some(output_path) = args.output_path.as_ref() {
-            ImageFormat::from_path(output_path)?
-        } else {
-            ImageFormat::Png
-        };
+        writer.write_all(&output_data)?;
+    }
 
-        let mut image_data = Vec::new();
-        output_image.write_to(&mut Cursor::new(&mut image_data), image_format)?;
+    Ok(ExitCode::SUCCESS)
+}
 
-        writer.write_all(&image_data)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_format_args(args: &[&str]) -> Result<ProcessArgs, clap::Error> {
+        MlaaArgs::try_parse_from(["mlaa_image"].iter().chain(args)).map(|args| args.process_args)
     }
 
-    Ok(ExitCode::SUCCESS)
+    #[test]
+    fn format_names_and_aliases() {
+        for (format_name, image_format) in [
+            ("png", ImageFormat::Png),
+            ("tif", ImageFormat::Tiff),
+            ("tiff", ImageFormat::Tiff),
+            ("webp", ImageFormat::WebP),
+            ("pam", ImageFormat::Pnm),
+            ("ppm", ImageFormat::Pnm),
+            ("exr", ImageFormat::OpenExr),
+        ] {
+            let process_args =
+                parse_format_args(&["--input-format", format_name, "--output-format", format_name]).unwrap();
+            assert_eq!(process_args.input_format, Some(image_format));
+            assert_eq!(process_args.output_format, Some(image_format));
+        }
+    }
+
+    #[test]
+    fn unknown_format_error_lists_formats() {
+        let err = parse_format_args(&["--output-format", "jpeg"])
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("invalid value 'jpeg'"), "{}", err);
+        assert!(err.contains("png, gif, bmp, tga, tiff, webp, qoi, pnm, exr"), "{}", err);
+    }
 }
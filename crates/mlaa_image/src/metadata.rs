@@ -0,0 +1,419 @@
+use std::error::Error;
+use std::io::{Cursor, Seek, Write};
+
+use image::{DynamicImage, ImageFormat};
+use png::{BitDepth, ColorType, PixelDimensions, ScaledFloat, SourceChromaticities, SrgbRenderingIntent, Unit};
+use tiff::decoder::ifd::Value;
+use tiff::decoder::Decoder as TiffDecoder;
+use tiff::encoder::{colortype, ImageEncoder, Rational, TiffEncoder, TiffKind};
+use tiff::tags::{ResolutionUnit, Tag};
+
+use crate::provenance::PROVENANCE_KEYWORD;
+
+const TIFF_TAG_ICC_PROFILE: Tag = Tag::Unknown(34675);
+
+// PNG text keywords having a TIFF tag counterpart. Text chunks with any
+// other keyword are dropped when converting from PNG to TIFF. The provenance
+// record is stored in a tag from the private, reusable 65000..=65535 range.
+const TIFF_TEXT_TAGS: [(&str, Tag); 6] = [
+    ("Description", Tag::ImageDescription),
+    ("Author", Tag::Artist),
+    ("Copyright", Tag::Copyright),
+    ("Software", Tag::Software),
+    ("Creation Time", Tag::DateTime),
+    (PROVENANCE_KEYWORD, TIFF_TAG_PROVENANCE),
+];
+
+// TIFF ASCII tags can't hold other characters, so the provenance record is
+// written as UTF-8 bytes instead. Older files have it as an ASCII tag.
+const TIFF_TAG_PROVENANCE: Tag = Tag::Unknown(65000);
+
+// Resolution of a TIFF file as stored in it, so TIFF to TIFF conversions keep
+// the unit and the exact rationals instead of going through the pixels per
+// meter of PNG.
+// The resolutions are numerator and denominator pairs.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TiffResolution {
+    pub unit: ResolutionUnit,
+    pub x: (u32, u32),
+    pub y: (u32, u32),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    Latin1,
+    CompressedLatin1,
+    Utf8,
+}
+
+#[derive(Clone)]
+pub struct TextEntry {
+    pub keyword: String,
+    pub text: String,
+    pub encoding: TextEncoding,
+}
+
+#[derive(Clone, Default)]
+pub struct ImageMetadata {
+    pub icc_profile: Option<Vec<u8>>,
+    pub srgb: Option<SrgbRenderingIntent>,
+    pub gamma: Option<ScaledFloat>,
+    pub chromaticities: Option<SourceChromaticities>,
+    pub pixel_dims: Option<PixelDimensions>,
+    pub tiff_resolution: Option<TiffResolution>,
+    pub text: Vec<TextEntry>,
+}
+
+impl ImageMetadata {
+    pub fn read(image_data: &[u8], image_format: ImageFormat) -> Result<ImageMetadata, Box<dyn Error>> {
+        match image_format {
+            ImageFormat::Png => ImageMetadata::read_png(image_data),
+            ImageFormat::Tiff => ImageMetadata::read_tiff(image_data),
+            _ => Ok(ImageMetadata::default()),
+        }
+    }
+
+    fn read_png(png_data: &[u8]) -> Result<ImageMetadata, Box<dyn Error>> {
+        let reader = png::Decoder::new(Cursor::new(png_data)).read_info()?;
+        let info = reader.info();
+
+        let mut text = Vec::new();
+        for chunk in &info.uncompressed_latin1_text {
+            text.push(TextEntry {
+                keyword: chunk.keyword.clone(),
+                text: chunk.text.clone(),
+                encoding: TextEncoding::Latin1,
+            });
+        }
+        for chunk in &info.compressed_latin1_text {
+            text.push(TextEntry {
+                keyword: chunk.keyword.clone(),
+                text: chunk.get_text()?,
+                encoding: TextEncoding::CompressedLatin1,
+            });
+        }
+        for chunk in &info.utf8_text {
+            text.push(TextEntry {
+                keyword: chunk.keyword.clone(),
+                text: chunk.get_text()?,
+                encoding: TextEncoding::Utf8,
+            });
+        }
+
+        Ok(ImageMetadata {
+            icc_profile: info.icc_profile.as_ref().map(|icc_profile| icc_profile.to_vec()),
+            srgb: info.srgb,
+            gamma: info.source_gamma,
+            chromaticities: info.source_chromaticities,
+            pixel_dims: info.pixel_dims,
+            tiff_resolution: None,
+            text,
+        })
+    }
+
+    fn read_tiff(tiff_data: &[u8]) -> Result<ImageMetadata, Box<dyn Error>> {
+        let mut decoder = TiffDecoder::new(Cursor::new(tiff_data))?;
+
+        let icc_profile = decoder.find_tag_unsigned_vec::<u8>(TIFF_TAG_ICC_PROFILE)?;
+
+        fn rational_value(value: Value) -> Option<(u32, u32)> {
+            match value {
+                Value::Rational(n, d) if d != 0 => Some((n, d)),
+                Value::RationalBig(n, d) if d != 0 => Some((u32::try_from(n).ok()?, u32::try_from(d).ok()?)),
+                _ => None,
+            }
+        }
+
+        let x_resolution = decoder.find_tag(Tag::XResolution)?.and_then(rational_value);
+        let y_resolution = decoder.find_tag(Tag::YResolution)?.and_then(rational_value);
+        let resolution_unit = decoder
+            .find_tag_unsigned::<u16>(Tag::ResolutionUnit)?
+            .and_then(ResolutionUnit::from_u16)
+            .unwrap_or(ResolutionUnit::Inch);
+
+        let tiff_resolution = x_resolution.zip(y_resolution).map(|(x, y)| TiffResolution {
+            unit: resolution_unit,
+            x,
+            y,
+        });
+
+        // PNG only knows about pixels per meter
+        let pixel_dims = tiff_resolution.map(|resolution| {
+            let (scale, unit) = match resolution.unit {
+                ResolutionUnit::Inch => (1.0 / 0.0254, Unit::Meter),
+                ResolutionUnit::Centimeter => (100.0, Unit::Meter),
+                _ => (1.0, Unit::Unspecified),
+            };
+            let pixels_per_unit = |(n, d): (u32, u32)| (n as f64 / d as f64 * scale).round() as u32;
+
+            PixelDimensions {
+                xppu: pixels_per_unit(resolution.x),
+                yppu: pixels_per_unit(resolution.y),
+                unit,
+            }
+        });
+
+        let mut text = Vec::new();
+        for (keyword, tag) in TIFF_TEXT_TAGS {
+            let text_value = match decoder.find_tag(tag)? {
+                Some(Value::Ascii(text)) => text,
+                Some(_) if tag == TIFF_TAG_PROVENANCE => {
+                    String::from_utf8(decoder.find_tag_unsigned_vec::<u8>(tag)?.unwrap_or_default())?
+                }
+                Some(value) => value.into_string()?,
+                None => continue,
+            };
+
+            text.push(TextEntry {
+                keyword: keyword.to_owned(),
+                text: text_value,
+                encoding: TextEncoding::Latin1,
+            });
+        }
+
+        Ok(ImageMetadata {
+            icc_profile,
+            pixel_dims,
+            tiff_resolution,
+            text,
+            ..ImageMetadata::default()
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.icc_profile.is_none()
+            && self.srgb.is_none()
+            && self.gamma.is_none()
+            && self.chromaticities.is_none()
+            && self.pixel_dims.is_none()
+            && self.tiff_resolution.is_none()
+            && self.text.is_empty()
+    }
+
+    pub fn png_encoder<W: Write>(
+        &self,
+        w: W,
+        width: u32,
+        height: u32,
+    ) -> Result<png::Encoder<'static, W>, Box<dyn Error>> {
+        let mut info = png::Info::with_size(width, height);
+        info.icc_profile = self.icc_profile.clone().map(Into::into);
+        info.pixel_dims = self.pixel_dims;
+
+        let mut encoder = png::Encoder::with_info(w, info)?;
+
+        if let Some(srgb) = self.srgb {
+            encoder.set_source_srgb(srgb);
+        }
+        if let Some(gamma) = self.gamma {
+            encoder.set_source_gamma(gamma);
+        }
+        if let Some(chromaticities) = self.chromaticities {
+            encoder.set_source_chromaticities(chromaticities);
+        }
+
+        for entry in &self.text {
+            match entry.encoding {
+                TextEncoding::Latin1 => encoder.add_text_chunk(entry.keyword.clone(), entry.text.clone())?,
+                TextEncoding::CompressedLatin1 => encoder.add_ztxt_chunk(entry.keyword.clone(), entry.text.clone())?,
+                TextEncoding::Utf8 => encoder.add_itxt_chunk(entry.keyword.clone(), entry.text.clone())?,
+            }
+        }
+
+        Ok(encoder)
+    }
+
+    pub fn encode_png(&self, image: &DynamicImage) -> Result<Vec<u8>, Box<dyn Error>> {
+        fn be_bytes(samples: &[u16]) -> Vec<u8> {
+            samples.iter().flat_map(|sample| sample.to_be_bytes()).collect()
+        }
+
+        let (color_type, bit_depth, image_data) = match image {
+            DynamicImage::ImageLuma8(image) => (ColorType::Grayscale, BitDepth::Eight, image.to_vec()),
+            DynamicImage::ImageLumaA8(image) => (ColorType::GrayscaleAlpha, BitDepth::Eight, image.to_vec()),
+            DynamicImage::ImageRgb8(image) => (ColorType::Rgb, BitDepth::Eight, image.to_vec()),
+            DynamicImage::ImageRgba8(image) => (ColorType::Rgba, BitDepth::Eight, image.to_vec()),
+            DynamicImage::ImageLuma16(image) => (ColorType::Grayscale, BitDepth::Sixteen, be_bytes(image)),
+            DynamicImage::ImageLumaA16(image) => (ColorType::GrayscaleAlpha, BitDepth::Sixteen, be_bytes(image)),
+            DynamicImage::ImageRgb16(image) => (ColorType::Rgb, BitDepth::Sixteen, be_bytes(image)),
+            DynamicImage::ImageRgba16(image) => (ColorType::Rgba, BitDepth::Sixteen, be_bytes(image)),
+            _ => return Err(format!("Unsupported PNG color type {:?}", image.color()).into()),
+        };
+
+        let mut png_data = Vec::new();
+
+        {
+            let mut encoder = self.png_encoder(&mut png_data, image.width(), image.height())?;
+            encoder.set_color(color_type);
+            encoder.set_depth(bit_depth);
+
+            let mut writer = encoder.write_header()?;
+            writer.write_image_data(&image_data)?;
+        }
+
+        Ok(png_data)
+    }
+
+    pub fn encode_tiff(&self, image: &DynamicImage) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut tiff_data = Vec::new();
+
+        {
+            let mut encoder = TiffEncoder::new(Cursor::new(&mut tiff_data))?;
+
+            macro_rules! write_tiff_image {
+                ($color_type:ty, $image:expr) => {{
+                    let mut tiff_image = encoder.new_image::<$color_type>(image.width(), image.height())?;
+                    self.write_tiff_tags(&mut tiff_image)?;
+                    tiff_image.write_data($image)?;
+                }};
+            }
+
+            match image {
+                DynamicImage::ImageLuma8(image) => write_tiff_image!(colortype::Gray8, image),
+                DynamicImage::ImageRgb8(image) => write_tiff_image!(colortype::RGB8, image),
+                DynamicImage::ImageRgba8(image) => write_tiff_image!(colortype::RGBA8, image),
+                DynamicImage::ImageLuma16(image) => write_tiff_image!(colortype::Gray16, image),
+                DynamicImage::ImageRgb16(image) => write_tiff_image!(colortype::RGB16, image),
+                DynamicImage::ImageRgba16(image) => write_tiff_image!(colortype::RGBA16, image),
+                _ => return Err(format!("Unsupported TIFF color type {:?}", image.color()).into()),
+            }
+        }
+
+        Ok(tiff_data)
+    }
+
+    fn write_tiff_tags<W, C, K>(&self, tiff_image: &mut ImageEncoder<W, C, K>) -> Result<(), Box<dyn Error>>
+    where
+        W: Write + Seek,
+        C: colortype::ColorType,
+        K: TiffKind,
+    {
+        if let Some(resolution) = self.tiff_resolution {
+            tiff_image.resolution_unit(resolution.unit);
+            tiff_image.x_resolution(Rational {
+                n: resolution.x.0,
+                d: resolution.x.1,
+            });
+            tiff_image.y_resolution(Rational {
+                n: resolution.y.0,
+                d: resolution.y.1,
+            });
+        } else if let Some(pixel_dims) = self.pixel_dims {
+            let unit = match pixel_dims.unit {
+                Unit::Meter => ResolutionUnit::Centimeter,
+                Unit::Unspecified => ResolutionUnit::None,
+            };
+            let denominator = if pixel_dims.unit == Unit::Meter { 100 } else { 1 };
+
+            tiff_image.resolution_unit(unit);
+            tiff_image.x_resolution(Rational {
+                n: pixel_dims.xppu,
+                d: denominator,
+            });
+            tiff_image.y_resolution(Rational {
+                n: pixel_dims.yppu,
+                d: denominator,
+            });
+        }
+
+        if let Some(icc_profile) = self.icc_profile.as_ref() {
+            tiff_image
+                .encoder()
+                .write_tag(TIFF_TAG_ICC_PROFILE, icc_profile.as_slice())?;
+        }
+
+        for (keyword, tag) in TIFF_TEXT_TAGS {
+            let Some(entry) = self.text.iter().find(|entry| entry.keyword == keyword) else {
+                continue;
+            };
+
+            if tag == TIFF_TAG_PROVENANCE {
+                tiff_image.encoder().write_tag(tag, entry.text.as_bytes())?;
+            } else if entry.text.is_ascii() && !entry.text.contains('\0') {
+                tiff_image.encoder().write_tag(tag, entry.text.as_str())?;
+            } else {
+                eprintln!(
+                    "mlaa_image: TIFF can't store the non-ASCII \"{}\" text, dropping it",
+                    keyword
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    fn tiff_image() -> DynamicImage {
+        RgbImage::from_pixel(3, 2, Rgb([10, 20, 30])).into()
+    }
+
+    #[test]
+    fn tiff_resolution_is_kept() {
+        for unit in [ResolutionUnit::Inch, ResolutionUnit::Centimeter, ResolutionUnit::None] {
+            let metadata = ImageMetadata {
+                tiff_resolution: Some(TiffResolution {
+                    unit,
+                    x: (7200, 100),
+                    y: (1, 3),
+                }),
+                ..ImageMetadata::default()
+            };
+
+            let tiff_data = metadata.encode_tiff(&tiff_image()).unwrap();
+            let read_metadata = ImageMetadata::read(&tiff_data, ImageFormat::Tiff).unwrap();
+            assert_eq!(read_metadata.tiff_resolution, metadata.tiff_resolution);
+
+            let tiff_data = read_metadata.encode_tiff(&tiff_image()).unwrap();
+            let read_metadata = ImageMetadata::read(&tiff_data, ImageFormat::Tiff).unwrap();
+            assert_eq!(read_metadata.tiff_resolution, metadata.tiff_resolution);
+        }
+    }
+
+    #[test]
+    fn tiff_resolution_converts_to_png() {
+        let metadata = ImageMetadata {
+            tiff_resolution: Some(TiffResolution {
+                unit: ResolutionUnit::Inch,
+                x: (72, 1),
+                y: (300, 1),
+            }),
+            ..ImageMetadata::default()
+        };
+
+        let tiff_data = metadata.encode_tiff(&tiff_image()).unwrap();
+        let pixel_dims = ImageMetadata::read(&tiff_data, ImageFormat::Tiff)
+            .unwrap()
+            .pixel_dims
+            .unwrap();
+        assert_eq!(
+            (pixel_dims.xppu, pixel_dims.yppu, pixel_dims.unit),
+            (2835, 11811, Unit::Meter)
+        );
+    }
+
+    #[test]
+    fn non_ascii_provenance_survives_tiff() {
+        let provenance_text = "config_paths = [\"/home/zoë/.mlaa\"]\n";
+        let metadata = ImageMetadata {
+            text: vec![TextEntry {
+                keyword: PROVENANCE_KEYWORD.to_owned(),
+                text: provenance_text.to_owned(),
+                encoding: TextEncoding::Utf8,
+            }],
+            ..ImageMetadata::default()
+        };
+
+        let tiff_data = metadata.encode_tiff(&tiff_image()).unwrap();
+        let read_metadata = ImageMetadata::read(&tiff_data, ImageFormat::Tiff).unwrap();
+
+        assert_eq!(read_metadata.text.len(), 1);
+        assert_eq!(read_metadata.text[0].keyword, PROVENANCE_KEYWORD);
+        assert_eq!(read_metadata.text[0].text, provenance_text);
+    }
+}
@@ -0,0 +1,814 @@
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use image::{imageops, DynamicImage, ImageFormat, Rgba, RgbaImage};
+use quick_xml::events::{BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use mlaa_impl::{mlaa_dynamic_image, MlaaOptions};
+
+use crate::config::{resolve_layer_config, ResolvedConfig, RULE_MATCH_OPTIONS};
+use crate::metadata::ImageMetadata;
+use crate::overlay::overlay_image;
+use crate::pipeline::{encode_image, output_metadata, process_image};
+use crate::ProcessArgs;
+
+// OpenRaster files are zip archives of PNG layers, their structure is
+// described by `stack.xml`. The layers are processed one by one, the rest of
+// the archive is copied unchanged, only the merged image and the thumbnail
+// are redrawn from the processed layers.
+
+const ORA_MIME_TYPE: &str = "image/openraster";
+const STACK_XML_PATH: &str = "stack.xml";
+const MERGED_IMAGE_PATH: &str = "mergedimage.png";
+const THUMBNAIL_PATH: &str = "Thumbnails/thumbnail.png";
+const THUMBNAIL_SIZE: u32 = 256;
+
+// The first entry of every ORA file is the uncompressed `mimetype` file.
+pub fn is_ora(data: &[u8]) -> bool {
+    if !data.starts_with(b"PK\x03\x04") || (data.get(30..38) != Some(b"mimetype")) {
+        return false;
+    }
+
+    let extra_field_length = u16::from_le_bytes([data[28], data[29]]) as usize;
+    data.get(38 + extra_field_length..)
+        .is_some_and(|content| content.starts_with(ORA_MIME_TYPE.as_bytes()))
+}
+
+pub fn is_ora_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("ora"))
+}
+
+// ORA files are always written as ORA, the output format only needs checking.
+pub fn check_ora_output(output_path: Option<&Path>, args: &ProcessArgs) -> Result<(), Box<dyn Error>> {
+    if args.output_format.is_some() || output_path.is_some_and(|output_path| !is_ora_path(output_path)) {
+        Err("ORA input can only be written as ORA".into())
+    } else {
+        Ok(())
+    }
+}
+
+struct ItemAttributes {
+    name: String,
+    x: i64,
+    y: i64,
+    opacity: f32,
+    visible: bool,
+    composite_op: String,
+}
+
+struct OraLayer {
+    index: usize,
+    src: String,
+    attributes: ItemAttributes,
+}
+
+struct OraStack {
+    attributes: ItemAttributes,
+    children: Vec<OraItem>,
+}
+
+enum OraItem {
+    Layer(OraLayer),
+    Stack(OraStack),
+}
+
+struct OraDocument {
+    width: u32,
+    height: u32,
+    root: OraStack,
+}
+
+fn attribute<T: FromStr>(element: &BytesStart, name: &str) -> Result<Option<T>, Box<dyn Error>> {
+    let Some(attribute) = element.try_get_attribute(name)? else {
+        return Ok(None);
+    };
+
+    let value = attribute.unescape_value()?;
+    value.trim().parse().map(Some).map_err(|_| {
+        format!(
+            "Invalid value \"{}\" for the `{}` attribute in {}",
+            value, name, STACK_XML_PATH
+        )
+        .into()
+    })
+}
+
+fn item_attributes(element: &BytesStart) -> Result<ItemAttributes, Box<dyn Error>> {
+    Ok(ItemAttributes {
+        name: attribute(element, "name")?.unwrap_or_default(),
+        x: attribute(element, "x")?.unwrap_or(0),
+        y: attribute(element, "y")?.unwrap_or(0),
+        opacity: attribute(element, "opacity")?.unwrap_or(1.0),
+        visible: attribute::<String>(element, "visibility")?.as_deref() != Some("hidden"),
+        composite_op: attribute(element, "composite-op")?.unwrap_or_else(|| "svg:src-over".to_owned()),
+    })
+}
+
+fn parse_stack_xml(stack_xml: &str) -> Result<OraDocument, Box<dyn Error>> {
+    let mut reader = Reader::from_str(stack_xml);
+
+    let mut image_size = None;
+    let mut open_stacks: Vec<OraStack> = Vec::new();
+    let mut root = None;
+    let mut layer_count = 0;
+
+    let mut add_item = |open_stacks: &mut Vec<OraStack>, item: OraItem| -> Result<(), Box<dyn Error>> {
+        match (open_stacks.last_mut(), item) {
+            (Some(parent), item) => parent.children.push(item),
+            (None, OraItem::Stack(stack)) if root.is_none() => root = Some(stack),
+            _ => return Err(format!("Invalid {}, layer outside of the root stack", STACK_XML_PATH).into()),
+        }
+        Ok(())
+    };
+
+    loop {
+        let (element, is_empty) = match reader.read_event()? {
+            Event::Start(element) => (element, false),
+            Event::Empty(element) => (element, true),
+            Event::End(element) if element.name().as_ref() == b"stack" => {
+                let stack = open_stacks
+                    .pop()
+                    .ok_or_else(|| format!("Invalid {}, unbalanced stacks", STACK_XML_PATH))?;
+                add_item(&mut open_stacks, OraItem::Stack(stack))?;
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+
+        match element.name().as_ref() {
+            b"image" => {
+                image_size = Some((
+                    attribute(&element, "w")?.unwrap_or(0),
+                    attribute(&element, "h")?.unwrap_or(0),
+                ));
+            }
+            b"stack" => {
+                let stack = OraStack {
+                    attributes: item_attributes(&element)?,
+                    children: Vec::new(),
+                };
+
+                if is_empty {
+                    add_item(&mut open_stacks, OraItem::Stack(stack))?;
+                } else {
+                    open_stacks.push(stack);
+                }
+            }
+            b"layer" => {
+                let layer = OraLayer {
+                    index: layer_count,
+                    src: attribute(&element, "src")?
+                        .ok_or_else(|| format!("Invalid {}, layer without `src` attribute", STACK_XML_PATH))?,
+                    attributes: item_attributes(&element)?,
+                };
+                layer_count += 1;
+
+                add_item(&mut open_stacks, OraItem::Layer(layer))?;
+            }
+            _ => {}
+        }
+    }
+
+    let (width, height) = image_size.ok_or_else(|| format!("Invalid {}, no image element", STACK_XML_PATH))?;
+    let root = root.ok_or_else(|| format!("Invalid {}, no root stack", STACK_XML_PATH))?;
+
+    Ok(OraDocument { width, height, root })
+}
+
+// Layer paths are made of the names of the stacks containing the layer and
+// the name of the layer, like `group/layer`. The root stack is not part of
+// the path.
+fn collect_layers<'a>(stack: &'a OraStack, stack_path: &str, layers: &mut Vec<(String, &'a OraLayer)>) {
+    let child_path = |name: &str| {
+        if stack_path.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{}/{}", stack_path, name)
+        }
+    };
+
+    for item in &stack.children {
+        match item {
+            OraItem::Layer(layer) => layers.push((child_path(&layer.attributes.name), layer)),
+            OraItem::Stack(child_stack) => {
+                collect_layers(child_stack, &child_path(&child_stack.attributes.name), layers)
+            }
+        }
+    }
+}
+
+fn read_entry(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut entry = archive
+        .by_name(name)
+        .map_err(|_| format!("\"{}\" is missing from the ORA file", name))?;
+
+    let mut entry_data = Vec::new();
+    entry.read_to_end(&mut entry_data)?;
+    Ok(entry_data)
+}
+
+fn read_stack_xml(archive: &mut ZipArchive<Cursor<&[u8]>>) -> Result<String, Box<dyn Error>> {
+    Ok(String::from_utf8(read_entry(archive, STACK_XML_PATH)?)?)
+}
+
+pub struct LayerJob {
+    pub layer_path: String,
+    layer_index: usize,
+    layer_name: String,
+    src: String,
+    pub mlaa_options: MlaaOptions,
+    pub config_paths: Vec<PathBuf>,
+}
+
+// Layers are selected by the --layers patterns, matched against the layer
+// names and paths. Without patterns the layers matched by the rules of the
+// config files are selected, or every layer when no rule matches any of
+// them.
+pub fn ora_layer_jobs(
+    ora_data: &[u8],
+    input_path: Option<&Path>,
+    resolved_config: &ResolvedConfig,
+    args: &ProcessArgs,
+) -> Result<Vec<LayerJob>, Box<dyn Error>> {
+    let mut archive = ZipArchive::new(Cursor::new(ora_data))?;
+    let document = parse_stack_xml(&read_stack_xml(&mut archive)?)?;
+
+    let mut layers = Vec::new();
+    collect_layers(&document.root, "", &mut layers);
+
+    let mut layer_jobs = Vec::new();
+    let mut rule_matched_layer_jobs = Vec::new();
+
+    for (layer_path, layer) in layers {
+        if !args.layer_patterns.is_empty()
+            && !args.layer_patterns.iter().any(|pattern| {
+                pattern.matches(&layer.attributes.name) || pattern.matches_with(&layer_path, RULE_MATCH_OPTIONS)
+            })
+        {
+            continue;
+        }
+
+        let layer_config =
+            resolve_layer_config(input_path, &layer_path, args.config_path.as_deref(), &args.option_args)?;
+        for warning in &layer_config.warnings {
+            if !resolved_config.warnings.contains(warning) {
+                eprintln!("mlaa_image: {}", warning);
+            }
+        }
+
+        let layer_job = LayerJob {
+            layer_path,
+            layer_index: layer.index,
+            layer_name: layer.attributes.name.clone(),
+            src: layer.src.clone(),
+            mlaa_options: layer_config.options,
+            config_paths: layer_config.config_paths,
+        };
+
+        if layer_config.layer_rule_patterns.is_empty() {
+            layer_jobs.push(layer_job);
+        } else {
+            rule_matched_layer_jobs.push(layer_job);
+        }
+    }
+
+    if !args.layer_patterns.is_empty() && layer_jobs.is_empty() && rule_matched_layer_jobs.is_empty() {
+        return Err("No layer of the ORA file matches the --layers patterns".into());
+    }
+
+    if args.layer_patterns.is_empty() && !rule_matched_layer_jobs.is_empty() {
+        Ok(rule_matched_layer_jobs)
+    } else {
+        layer_jobs.extend(rule_matched_layer_jobs);
+        layer_jobs.sort_by_key(|layer_job| layer_job.layer_index);
+        Ok(layer_jobs)
+    }
+}
+
+// Names the overlay layer file after the layer it belongs to, `data/ink.png`
+// becomes `data/ink-mlaa.png`.
+fn overlay_entry_name(src: &str, entry_names: &mut HashSet<String>) -> String {
+    let stem = src.strip_suffix(".png").unwrap_or(src);
+
+    let entry_name = (1..)
+        .map(|counter| {
+            if counter == 1 {
+                format!("{}-mlaa.png", stem)
+            } else {
+                format!("{}-mlaa-{}.png", stem, counter)
+            }
+        })
+        .find(|entry_name| !entry_names.contains(entry_name))
+        .unwrap();
+
+    entry_names.insert(entry_name.clone());
+    entry_name
+}
+
+// Overlay layers are inserted right above their layers, with the same offset,
+// opacity and visibility.
+fn insert_overlay_layers(
+    stack_xml: &str,
+    overlay_layers: &BTreeMap<usize, (String, String)>,
+) -> Result<String, Box<dyn Error>> {
+    let mut reader = Reader::from_str(stack_xml);
+    let mut writer = Writer::new(Vec::new());
+    let mut layer_index = 0;
+    let mut indentation: Option<BytesText> = None;
+
+    loop {
+        let event = reader.read_event()?;
+
+        match &event {
+            Event::Start(element) | Event::Empty(element) if element.name().as_ref() == b"layer" => {
+                if let Some((overlay_name, overlay_src)) = overlay_layers.get(&layer_index) {
+                    let mut overlay_element = BytesStart::new("layer");
+                    overlay_element.push_attribute(("name", overlay_name.as_str()));
+                    overlay_element.push_attribute(("src", overlay_src.as_str()));
+
+                    for attribute in element.attributes() {
+                        let attribute = attribute?;
+                        if !matches!(attribute.key.as_ref(), b"name" | b"src" | b"selected") {
+                            overlay_element.push_attribute(attribute);
+                        }
+                    }
+
+                    writer.write_event(Event::Empty(overlay_element))?;
+                    if let Some(indentation) = indentation.as_ref() {
+                        writer.write_event(Event::Text(indentation.clone()))?;
+                    }
+                }
+
+                layer_index += 1;
+            }
+            Event::Text(text) => {
+                indentation = text
+                    .iter()
+                    .all(u8::is_ascii_whitespace)
+                    .then(|| text.clone().into_owned());
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+
+        writer.write_event(event)?;
+    }
+
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+// Premultiplied RGBA pixels.
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Canvas {
+        Canvas {
+            width,
+            height,
+            pixels: vec![[0.0; 4]; width as usize * height as usize],
+        }
+    }
+
+    fn from_image(image: &DynamicImage) -> Canvas {
+        let image = image.to_rgba32f();
+
+        Canvas {
+            width: image.width(),
+            height: image.height(),
+            pixels: image
+                .pixels()
+                .map(|&Rgba([r, g, b, a])| [r * a, g * a, b * a, a])
+                .collect(),
+        }
+    }
+
+    fn to_image(&self) -> RgbaImage {
+        RgbaImage::from_fn(self.width, self.height, |x, y| {
+            let [r, g, b, a] = self.pixels[(y * self.width + x) as usize];
+            let c = if a > 0.0 { [r / a, g / a, b / a, a] } else { [0.0; 4] };
+            Rgba(c.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8))
+        })
+    }
+
+    // Source over compositing.
+    fn draw(&mut self, source: &Canvas, x_offset: i64, y_offset: i64, opacity: f32) {
+        for source_y in 0..source.height {
+            let y = y_offset + source_y as i64;
+            if (y < 0) || (y >= self.height as i64) {
+                continue;
+            }
+
+            for source_x in 0..source.width {
+                let x = x_offset + source_x as i64;
+                if (x < 0) || (x >= self.width as i64) {
+                    continue;
+                }
+
+                let source_pixel = source.pixels[(source_y * source.width + source_x) as usize].map(|c| c * opacity);
+                let pixel = &mut self.pixels[(y as u32 * self.width + x as u32) as usize];
+
+                for channel in 0..4 {
+                    pixel[channel] = source_pixel[channel] + pixel[channel] * (1.0 - source_pixel[3]);
+                }
+            }
+        }
+    }
+}
+
+// Only the normal blending mode is supported for redrawing the merged image.
+fn is_drawable(stack: &OraStack) -> bool {
+    stack.children.iter().all(|item| match item {
+        OraItem::Layer(layer) => !layer.attributes.visible || (layer.attributes.composite_op == "svg:src-over"),
+        OraItem::Stack(child_stack) => {
+            !child_stack.attributes.visible
+                || ((child_stack.attributes.composite_op == "svg:src-over") && is_drawable(child_stack))
+        }
+    })
+}
+
+// Stacks are drawn as isolated groups, the first child of a stack is the
+// topmost one.
+fn draw_stack(
+    stack: &OraStack,
+    width: u32,
+    height: u32,
+    layer_image: &mut impl FnMut(&str) -> Result<DynamicImage, Box<dyn Error>>,
+) -> Result<Canvas, Box<dyn Error>> {
+    let mut canvas = Canvas::new(width, height);
+
+    for item in stack.children.iter().rev() {
+        match item {
+            OraItem::Layer(layer) if layer.attributes.visible => {
+                canvas.draw(
+                    &Canvas::from_image(&layer_image(&layer.src)?),
+                    layer.attributes.x,
+                    layer.attributes.y,
+                    layer.attributes.opacity,
+                );
+            }
+            OraItem::Stack(child_stack) if child_stack.attributes.visible => {
+                canvas.draw(
+                    &draw_stack(child_stack, width, height, layer_image)?,
+                    child_stack.attributes.x,
+                    child_stack.attributes.y,
+                    child_stack.attributes.opacity,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    Ok(canvas)
+}
+
+pub fn process_ora(ora_data: &[u8], layer_jobs: &[LayerJob], args: &ProcessArgs) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut archive = ZipArchive::new(Cursor::new(ora_data))?;
+    let stack_xml = read_stack_xml(&mut archive)?;
+
+    let mut entry_names = archive.file_names().map(str::to_owned).collect::<HashSet<_>>();
+    let mut new_entries = BTreeMap::new();
+    let mut overlay_layers = BTreeMap::new();
+
+    for layer_job in layer_jobs {
+        eprintln!("mlaa_image: Processing layer \"{}\"", layer_job.layer_path);
+
+        let layer_error =
+            |err: Box<dyn Error>| -> Box<dyn Error> { format!("Layer \"{}\": {}", layer_job.layer_path, err).into() };
+
+        let layer_data = read_entry(&mut archive, &layer_job.src)?;
+
+        if args.ora_overlay {
+            let metadata = output_metadata(
+                &layer_data,
+                ImageFormat::Png,
+                &layer_job.mlaa_options,
+                layer_job.config_paths.clone(),
+                args,
+            )
+            .map_err(layer_error)?;
+
+            let input_image = image::load_from_memory_with_format(&layer_data, ImageFormat::Png)?;
+            let output_image = mlaa_dynamic_image(&input_image, &layer_job.mlaa_options);
+
+            let overlay_src = overlay_entry_name(&layer_job.src, &mut entry_names);
+            new_entries.insert(
+                overlay_src.clone(),
+                encode_image(
+                    overlay_image(&input_image, &output_image).map_err(layer_error)?,
+                    ImageFormat::Png,
+                    &metadata,
+                )?,
+            );
+            overlay_layers.insert(
+                layer_job.layer_index,
+                (format!("{} (MLAA)", layer_job.layer_name), overlay_src),
+            );
+        } else {
+            new_entries.insert(
+                layer_job.src.clone(),
+                process_image(
+                    &layer_data,
+                    ImageFormat::Png,
+                    ImageFormat::Png,
+                    &layer_job.mlaa_options,
+                    layer_job.config_paths.clone(),
+                    args,
+                )
+                .map_err(layer_error)?,
+            );
+        }
+    }
+
+    let stack_xml = if overlay_layers.is_empty() {
+        stack_xml
+    } else {
+        insert_overlay_layers(&stack_xml, &overlay_layers)?
+    };
+
+    let document = parse_stack_xml(&stack_xml)?;
+    if is_drawable(&document.root) {
+        let merged_image = draw_stack(&document.root, document.width, document.height, &mut |src| {
+            let layer_data = match new_entries.get(src) {
+                Some(layer_data) => layer_data.clone(),
+                None => read_entry(&mut archive, src)?,
+            };
+            Ok(image::load_from_memory_with_format(&layer_data, ImageFormat::Png)?)
+        })?
+        .to_image();
+
+        let scale = (THUMBNAIL_SIZE as f32 / document.width.max(document.height).max(1) as f32).min(1.0);
+        let thumbnail = imageops::thumbnail(
+            &merged_image,
+            ((document.width as f32 * scale).round() as u32).max(1),
+            ((document.height as f32 * scale).round() as u32).max(1),
+        );
+
+        new_entries.insert(
+            MERGED_IMAGE_PATH.to_owned(),
+            encode_image(merged_image.into(), ImageFormat::Png, &ImageMetadata::default())?,
+        );
+        new_entries.insert(
+            THUMBNAIL_PATH.to_owned(),
+            encode_image(thumbnail.into(), ImageFormat::Png, &ImageMetadata::default())?,
+        );
+    } else {
+        eprintln!("mlaa_image: ORA file uses blending modes other than normal, the merged image is not updated");
+    }
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    writer.start_file("mimetype", stored)?;
+    writer.write_all(ORA_MIME_TYPE.as_bytes())?;
+
+    writer.start_file(STACK_XML_PATH, deflated)?;
+    writer.write_all(stack_xml.as_bytes())?;
+
+    // Entries keep their order, the new entries are appended at the end.
+    for entry_index in 0..archive.len() {
+        let entry = archive.by_index_raw(entry_index)?;
+        let entry_name = entry.name().to_owned();
+
+        if (entry_name == "mimetype") || (entry_name == STACK_XML_PATH) {
+            continue;
+        }
+
+        if let Some(entry_data) = new_entries.remove(&entry_name) {
+            writer.start_file(entry_name, stored)?;
+            writer.write_all(&entry_data)?;
+        } else {
+            writer.raw_copy_file(entry)?;
+        }
+    }
+
+    for (entry_name, entry_data) in new_entries {
+        writer.start_file(entry_name, stored)?;
+        writer.write_all(&entry_data)?;
+    }
+
+    Ok(writer.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::config::resolve_config;
+    use crate::test_util::TempDir;
+    use crate::MlaaArgs;
+
+    const STACK_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<image w="16" h="16">
+  <stack>
+    <stack name="lineart" opacity="0.75">
+      <layer name="ink" src="data/ink.png" x="2" y="3" opacity="0.5"/>
+      <layer name="sketch" src="data/sketch.png" visibility="hidden"/>
+    </stack>
+    <layer name="background" src="data/background.png"/>
+  </stack>
+</image>
+"#;
+
+    const LAYER_SOURCES: [&str; 3] = ["data/ink.png", "data/sketch.png", "data/background.png"];
+
+    fn process_args(args: &[&str]) -> ProcessArgs {
+        MlaaArgs::parse_from(["mlaa_image"].iter().chain(args)).process_args
+    }
+
+    fn staircase_png() -> Vec<u8> {
+        let image = RgbaImage::from_fn(8, 8, |x, y| {
+            if x + y >= 8 {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([0, 0, 0, 255])
+            }
+        });
+
+        let mut png_data = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png_data), ImageFormat::Png)
+            .unwrap();
+        png_data
+    }
+
+    fn ora_file() -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        writer.start_file("mimetype", stored).unwrap();
+        writer.write_all(ORA_MIME_TYPE.as_bytes()).unwrap();
+        writer.start_file(STACK_XML_PATH, deflated).unwrap();
+        writer.write_all(STACK_XML.as_bytes()).unwrap();
+
+        for src in LAYER_SOURCES {
+            writer.start_file(src, deflated).unwrap();
+            writer.write_all(&staircase_png()).unwrap();
+        }
+
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn entries(ora_data: &[u8]) -> BTreeMap<String, Vec<u8>> {
+        let mut archive = ZipArchive::new(Cursor::new(ora_data)).unwrap();
+        let entry_names = archive.file_names().map(str::to_owned).collect::<Vec<_>>();
+
+        entry_names
+            .into_iter()
+            .map(|entry_name| {
+                let entry_data = read_entry(&mut archive, &entry_name).unwrap();
+                (entry_name, entry_data)
+            })
+            .collect()
+    }
+
+    // Path, source, offset, opacity and visibility of the layers, topmost first.
+    fn layers(ora_data: &[u8]) -> Vec<(String, String, i64, i64, f32, bool)> {
+        let mut archive = ZipArchive::new(Cursor::new(ora_data)).unwrap();
+        let document = parse_stack_xml(&read_stack_xml(&mut archive).unwrap()).unwrap();
+
+        let mut layers = Vec::new();
+        collect_layers(&document.root, "", &mut layers);
+
+        layers
+            .into_iter()
+            .map(|(layer_path, layer)| {
+                let attributes = &layer.attributes;
+                (
+                    layer_path,
+                    layer.src.clone(),
+                    attributes.x,
+                    attributes.y,
+                    attributes.opacity,
+                    attributes.visible,
+                )
+            })
+            .collect()
+    }
+
+    fn layer(
+        layer_path: &str,
+        src: &str,
+        x: i64,
+        y: i64,
+        opacity: f32,
+        visible: bool,
+    ) -> (String, String, i64, i64, f32, bool) {
+        (layer_path.to_owned(), src.to_owned(), x, y, opacity, visible)
+    }
+
+    fn input_layers() -> Vec<(String, String, i64, i64, f32, bool)> {
+        vec![
+            layer("lineart/ink", "data/ink.png", 2, 3, 0.5, true),
+            layer("lineart/sketch", "data/sketch.png", 0, 0, 1.0, false),
+            layer("background", "data/background.png", 0, 0, 1.0, true),
+        ]
+    }
+
+    fn processed(ora_data: &[u8], input_path: Option<&Path>, args: &ProcessArgs) -> Vec<u8> {
+        let resolved_config = resolve_config(input_path, args.config_path.as_deref(), &args.option_args).unwrap();
+        let layer_jobs = ora_layer_jobs(ora_data, input_path, &resolved_config, args).unwrap();
+        process_ora(ora_data, &layer_jobs, args).unwrap()
+    }
+
+    // Sources of the layers whose data differs between the two files.
+    fn changed_layers(input_data: &[u8], output_data: &[u8]) -> Vec<&'static str> {
+        let (input_entries, output_entries) = (entries(input_data), entries(output_data));
+
+        LAYER_SOURCES
+            .into_iter()
+            .filter(|&src| input_entries[src] != output_entries[src])
+            .collect()
+    }
+
+    #[test]
+    fn layer_attributes_are_kept() {
+        let input_data = ora_file();
+        let output_data = processed(&input_data, None, &process_args(&[]));
+
+        assert_eq!(layers(&output_data), input_layers());
+        assert_eq!(changed_layers(&input_data, &output_data), LAYER_SOURCES);
+
+        let mut archive = ZipArchive::new(Cursor::new(&output_data[..])).unwrap();
+        let document = parse_stack_xml(&read_stack_xml(&mut archive).unwrap()).unwrap();
+        let OraItem::Stack(lineart) = &document.root.children[0] else {
+            panic!("The lineart stack is missing");
+        };
+        assert_eq!((document.width, document.height), (16, 16));
+        assert_eq!(lineart.attributes.name, "lineart");
+        assert_eq!(lineart.attributes.opacity, 0.75);
+        assert_eq!(lineart.children.len(), 2);
+    }
+
+    #[test]
+    fn only_the_selected_layers_change() {
+        let input_data = ora_file();
+
+        let output_data = processed(&input_data, None, &process_args(&["--layers", "ink"]));
+        assert_eq!(layers(&output_data), input_layers());
+        assert_eq!(changed_layers(&input_data, &output_data), ["data/ink.png"]);
+
+        let output_data = processed(&input_data, None, &process_args(&["--layers", "lineart/*"]));
+        assert_eq!(
+            changed_layers(&input_data, &output_data),
+            ["data/ink.png", "data/sketch.png"]
+        );
+
+        let temp_dir = TempDir::new("ora-rules");
+        temp_dir.write(
+            ".mlaa",
+            r#"
+[[rules]]
+path = "artwork.ora/lineart/sketch"
+strict_mode = false
+"#,
+        );
+        let input_path = temp_dir.write("artwork.ora", &input_data);
+
+        let output_data = processed(&input_data, Some(&input_path), &process_args(&[]));
+        assert_eq!(layers(&output_data), input_layers());
+        assert_eq!(changed_layers(&input_data, &output_data), ["data/sketch.png"]);
+    }
+
+    #[test]
+    fn overlay_layers_are_inserted_above_their_layers() {
+        let input_data = ora_file();
+        let output_data = processed(&input_data, None, &process_args(&["--ora-overlay", "--layers", "ink"]));
+
+        let mut expected_layers = input_layers();
+        expected_layers.insert(0, layer("lineart/ink (MLAA)", "data/ink-mlaa.png", 2, 3, 0.5, true));
+        assert_eq!(layers(&output_data), expected_layers);
+
+        assert!(changed_layers(&input_data, &output_data).is_empty());
+        assert!(entries(&output_data).contains_key("data/ink-mlaa.png"));
+    }
+
+    #[test]
+    fn mimetype_is_the_first_entry_and_stored() {
+        let output_data = processed(&ora_file(), None, &process_args(&["--ora-overlay"]));
+        assert!(is_ora(&output_data));
+
+        let mut archive = ZipArchive::new(Cursor::new(&output_data[..])).unwrap();
+        let mut mimetype = archive.by_index(0).unwrap();
+        assert_eq!(mimetype.name(), "mimetype");
+        assert_eq!(mimetype.compression(), CompressionMethod::Stored);
+
+        let mut mime_type = String::new();
+        mimetype.read_to_string(&mut mime_type).unwrap();
+        assert_eq!(mime_type, ORA_MIME_TYPE);
+    }
+}
@@ -0,0 +1,146 @@
+use std::error::Error;
+
+use image::{DynamicImage, ImageBuffer, ImageFormat, Pixel, Primitive, Rgba};
+
+use crate::animation::is_animated;
+use crate::metadata::ImageMetadata;
+use crate::pipeline::{decode_image, encode_image};
+
+// Overlay of the pixels changed by the antialiasing, every other pixel is
+// fully transparent.
+//
+// Drawing the overlay over the input image with normal alpha blending gives
+// the output image exactly where the changed pixels are opaque or the input
+// pixels are fully transparent. Elsewhere the translucent blended colors would
+// be mixed with the input pixels below them, these overlays are refused.
+pub fn overlay_image(input_image: &DynamicImage, output_image: &DynamicImage) -> Result<DynamicImage, Box<dyn Error>> {
+    let inexact_pixel_count = input_image
+        .to_rgba32f()
+        .pixels()
+        .zip(output_image.to_rgba32f().pixels())
+        .filter(|(input_pixel, output_pixel)| {
+            (input_pixel != output_pixel) && (output_pixel[3] < 1.0) && (input_pixel[3] > 0.0)
+        })
+        .count();
+
+    if inexact_pixel_count > 0 {
+        return Err(format!(
+            "The overlay can't reproduce the output, {} translucent overlay pixels would be drawn over visible input pixels",
+            inexact_pixel_count
+        )
+        .into());
+    }
+
+    let color_type = output_image.color();
+
+    Ok(match color_type.bytes_per_pixel() / color_type.channel_count() {
+        1 => overlay_buffer(&input_image.to_rgba8(), &output_image.to_rgba8()).into(),
+        2 => overlay_buffer(&input_image.to_rgba16(), &output_image.to_rgba16()).into(),
+        _ => overlay_buffer(&input_image.to_rgba32f(), &output_image.to_rgba32f()).into(),
+    })
+}
+
+fn overlay_buffer<T>(
+    input_image: &ImageBuffer<Rgba<T>, Vec<T>>,
+    output_image: &ImageBuffer<Rgba<T>, Vec<T>>,
+) -> ImageBuffer<Rgba<T>, Vec<T>>
+where
+    T: Primitive,
+    Rgba<T>: Pixel<Subpixel = T>,
+{
+    ImageBuffer::from_fn(output_image.width(), output_image.height(), |x, y| {
+        let output_pixel = *output_image.get_pixel(x, y);
+
+        if input_image.get_pixel_checked(x, y) == Some(&output_pixel) {
+            Rgba([T::DEFAULT_MIN_VALUE; 4])
+        } else {
+            output_pixel
+        }
+    })
+}
+
+// Overlay of an already processed image, both images are decoded again so
+// the overlay matches the written output file.
+pub fn encode_overlay(
+    input_data: &[u8],
+    input_format: ImageFormat,
+    output_data: &[u8],
+    output_format: ImageFormat,
+    overlay_format: ImageFormat,
+    metadata: &ImageMetadata,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    if is_animated(input_data, input_format)? {
+        return Err("--output-overlay doesn't support animated images".into());
+    }
+
+    let input_image = decode_image(input_data, input_format)?;
+    let output_image = decode_image(output_data, output_format)?;
+
+    encode_image(overlay_image(&input_image, &output_image)?, overlay_format, metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{imageops, Rgba, RgbaImage};
+
+    use mlaa_impl::{mlaa_dynamic_image, MlaaOptions};
+
+    use super::*;
+
+    // Encodes the input and the processed output as PNG files.
+    fn process_staircase(light_color: Rgba<u8>) -> (Vec<u8>, Vec<u8>) {
+        let input_image = DynamicImage::from(RgbaImage::from_fn(8, 8, |x, y| {
+            if x + y < 8 {
+                Rgba([0, 0, 0, 255])
+            } else {
+                light_color
+            }
+        }));
+        let output_image = mlaa_dynamic_image(&input_image, &MlaaOptions::default());
+
+        let metadata = ImageMetadata::default();
+        (
+            encode_image(input_image, ImageFormat::Png, &metadata).unwrap(),
+            encode_image(output_image, ImageFormat::Png, &metadata).unwrap(),
+        )
+    }
+
+    #[test]
+    fn composited_overlay_equals_the_output() {
+        let (input_data, output_data) = process_staircase(Rgba([255; 4]));
+        let overlay_data = encode_overlay(
+            &input_data,
+            ImageFormat::Png,
+            &output_data,
+            ImageFormat::Png,
+            ImageFormat::Png,
+            &ImageMetadata::default(),
+        )
+        .unwrap();
+
+        let overlay = decode_image(&overlay_data, ImageFormat::Png).unwrap().to_rgba8();
+        assert!(overlay.pixels().any(|pixel| pixel[3] != 0));
+
+        let mut composited = decode_image(&input_data, ImageFormat::Png).unwrap().to_rgba8();
+        imageops::overlay(&mut composited, &overlay, 0, 0);
+        assert_eq!(
+            composited,
+            decode_image(&output_data, ImageFormat::Png).unwrap().to_rgba8()
+        );
+    }
+
+    #[test]
+    fn inexact_overlays_are_refused() {
+        let (input_data, output_data) = process_staircase(Rgba([255, 255, 255, 128]));
+        let result = encode_overlay(
+            &input_data,
+            ImageFormat::Png,
+            &output_data,
+            ImageFormat::Png,
+            ImageFormat::Png,
+            &ImageMetadata::default(),
+        );
+
+        assert!(result.unwrap_err().to_string().contains("translucent overlay pixels"));
+    }
+}
@@ -0,0 +1,80 @@
+use std::error::Error;
+
+use image::{DynamicImage, ImageBuffer, LumaA, Rgba};
+
+// The PNM decoder of the `image` crate rejects PAM files with a
+// `GRAYSCALE_ALPHA` or `RGB_ALPHA` tuple type, these are decoded here instead.
+// Returns `None` for every other kind of PNM file.
+pub fn decode_pam_alpha(data: &[u8]) -> Result<Option<DynamicImage>, Box<dyn Error>> {
+    if !data.starts_with(b"P7\n") {
+        return Ok(None);
+    }
+
+    let header_end = data
+        .windows(8)
+        .position(|window| window == b"\nENDHDR\n")
+        .ok_or("PAM header has no ENDHDR line")?;
+    let header = std::str::from_utf8(&data[3..header_end])?;
+    let samples = &data[header_end + 8..];
+
+    let (mut width, mut height, mut depth, mut maxval, mut tupltype) = (None, None, None, None, None);
+
+    for line in header.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        match key {
+            "WIDTH" => width = Some(value.trim().parse::<u32>()?),
+            "HEIGHT" => height = Some(value.trim().parse::<u32>()?),
+            "DEPTH" => depth = Some(value.trim().parse::<usize>()?),
+            "MAXVAL" => maxval = Some(value.trim().parse::<u32>()?),
+            "TUPLTYPE" => tupltype = Some(value.trim()),
+            _ => return Err(format!("Unknown PAM header field \"{}\"", key).into()),
+        }
+    }
+
+    let (Some(width), Some(height), Some(depth), Some(maxval)) = (width, height, depth, maxval) else {
+        return Err("Incomplete PAM header".into());
+    };
+
+    if !matches!((tupltype, depth), (Some("GRAYSCALE_ALPHA"), 2) | (Some("RGB_ALPHA"), 4)) {
+        return Ok(None);
+    }
+
+    if !(1..=65535).contains(&maxval) {
+        return Err(format!("Invalid PAM maximum value {}", maxval).into());
+    }
+
+    let sample_count = width as usize * height as usize * depth;
+    let sample_size = if maxval < 256 { 1 } else { 2 };
+    if samples.len() < sample_count * sample_size {
+        return Err("PAM image data is truncated".into());
+    }
+
+    let samples = samples[..sample_count * sample_size]
+        .chunks_exact(sample_size)
+        .map(|sample| {
+            let value = sample.iter().fold(0u32, |value, &byte| (value << 8) | byte as u32);
+            value.min(maxval) as f32 / maxval as f32
+        });
+
+    let image = if maxval < 256 {
+        let samples = samples.map(|v| (v * 255.0).round() as u8).collect();
+        if depth == 2 {
+            ImageBuffer::<LumaA<u8>, _>::from_vec(width, height, samples).map(DynamicImage::from)
+        } else {
+            ImageBuffer::<Rgba<u8>, _>::from_vec(width, height, samples).map(DynamicImage::from)
+        }
+    } else {
+        let samples = samples.map(|v| (v * 65535.0).round() as u16).collect();
+        if depth == 2 {
+            ImageBuffer::<LumaA<u16>, _>::from_vec(width, height, samples).map(DynamicImage::from)
+        } else {
+            ImageBuffer::<Rgba<u16>, _>::from_vec(width, height, samples).map(DynamicImage::from)
+        }
+    };
+
+    Ok(image)
+}
@@ -0,0 +1,312 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use image::{DynamicImage, ImageFormat};
+
+use mlaa_impl::{convert_for_format, encode_dynamic_image, mlaa_dynamic_image, MlaaOptions};
+
+use crate::animation::{is_animated_gif, is_animated_png, process_apng, process_gif};
+use crate::indexed::{mlaa_process_indexed, mlaa_process_indexed_palette, IndexedImage};
+use crate::metadata::ImageMetadata;
+use crate::pam::decode_pam_alpha;
+use crate::provenance::{Provenance, PROVENANCE_KEYWORD};
+use crate::ProcessArgs;
+
+pub fn detect_image_format(
+    image_data: &[u8],
+    explicit_format: Option<ImageFormat>,
+    path: Option<&Path>,
+) -> Result<ImageFormat, Box<dyn Error>> {
+    if let Some(image_format) = explicit_format {
+        Ok(image_format)
+    } else if let Ok(image_format) = image::guess_format(image_data) {
+        Ok(image_format)
+    } else if let Some(path) = path {
+        Ok(ImageFormat::from_path(path)?)
+    } else {
+        Err("Unable to detect the input image format, use --input-format".into())
+    }
+}
+
+// Metadata of the output image, the metadata of the input image extended with
+// the provenance record.
+pub fn output_metadata(
+    input_data: &[u8],
+    input_format: ImageFormat,
+    mlaa_options: &MlaaOptions,
+    config_paths: Vec<PathBuf>,
+    args: &ProcessArgs,
+) -> Result<ImageMetadata, Box<dyn Error>> {
+    let mut metadata = ImageMetadata::read(input_data, input_format)?;
+
+    if let Some(provenance) = Provenance::find(&metadata)? {
+        if !args.reprocess {
+            return Err(format!(
+                "Input image was already processed by mlaa_image {}, use --reprocess to process it again",
+                provenance.tool_version
+            )
+            .into());
+        }
+    }
+
+    if args.strip_metadata {
+        metadata = ImageMetadata::default();
+    }
+
+    Provenance::new(mlaa_options, config_paths).embed(&mut metadata)?;
+    Ok(metadata)
+}
+
+pub fn process_image(
+    input_data: &[u8],
+    input_format: ImageFormat,
+    output_format: ImageFormat,
+    mlaa_options: &MlaaOptions,
+    config_paths: Vec<PathBuf>,
+    args: &ProcessArgs,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let metadata = output_metadata(input_data, input_format, mlaa_options, config_paths, args)?;
+
+    // GIF files keep their palettes when written as GIF, animated files keep
+    // their frames.
+    if (input_format == ImageFormat::Gif) && ((output_format == ImageFormat::Gif) || is_animated_gif(input_data)?) {
+        if output_format != ImageFormat::Gif {
+            return Err("Animated GIF input can only be written as GIF".into());
+        }
+
+        warn_unsupported_metadata(&metadata, output_format);
+
+        let (output_data, quantization_error) = process_gif(input_data, mlaa_options)?;
+        eprintln!(
+            "mlaa_image: Quantized {} blended pixels to the GIF palettes, mean error {:.2}, max error {:.2}",
+            quantization_error.pixel_count,
+            quantization_error.mean_error(),
+            quantization_error.max_error
+        );
+
+        return Ok(output_data);
+    }
+
+    if (input_format == ImageFormat::Png) && is_animated_png(input_data)? {
+        if output_format != ImageFormat::Png {
+            return Err("Animated PNG input can only be written as PNG".into());
+        }
+
+        return process_apng(input_data, mlaa_options, &metadata);
+    }
+
+    let indexed_image = if input_format == ImageFormat::Png {
+        IndexedImage::from_png(input_data)?
+    } else {
+        None
+    };
+
+    let output_data = if let Some(indexed_image) = indexed_image {
+        let output_indexed_image = if args.keep_palette {
+            if output_format != ImageFormat::Png {
+                eprintln!("mlaa_image: Indexed output is only supported for PNG files, writing truecolor image");
+                None
+            } else if let Some(output_indexed_image) = mlaa_process_indexed_palette(&indexed_image, mlaa_options) {
+                Some(output_indexed_image)
+            } else {
+                eprintln!(
+                    "mlaa_image: Not enough free palette entries for the blended colors, writing truecolor image"
+                );
+                None
+            }
+        } else {
+            None
+        };
+
+        if let Some(output_indexed_image) = output_indexed_image {
+            output_indexed_image.to_png(&metadata)?
+        } else {
+            encode_image(
+                mlaa_process_indexed(&indexed_image, mlaa_options),
+                output_format,
+                &metadata,
+            )?
+        }
+    } else {
+        encode_image(
+            mlaa_dynamic_image(&decode_image(input_data, input_format)?, mlaa_options),
+            output_format,
+            &metadata,
+        )?
+    };
+
+    Ok(output_data)
+}
+
+pub fn decode_image(image_data: &[u8], image_format: ImageFormat) -> Result<DynamicImage, Box<dyn Error>> {
+    let pam_image = if image_format == ImageFormat::Pnm {
+        decode_pam_alpha(image_data)?
+    } else {
+        None
+    };
+
+    if let Some(pam_image) = pam_image {
+        Ok(pam_image)
+    } else {
+        Ok(image::load_from_memory_with_format(image_data, image_format)?)
+    }
+}
+
+// The provenance record is added to every output, formats without a place for
+// it drop it silently. Metadata of the input is only warned about once per
+// output format, instead of for every file of a batch.
+fn warn_unsupported_metadata(metadata: &ImageMetadata, image_format: ImageFormat) {
+    static WARNED_FORMATS: Mutex<Vec<ImageFormat>> = Mutex::new(Vec::new());
+
+    let mut input_metadata = metadata.clone();
+    input_metadata.text.retain(|entry| entry.keyword != PROVENANCE_KEYWORD);
+
+    if input_metadata.is_empty() {
+        return;
+    }
+
+    let mut warned_formats = WARNED_FORMATS.lock().unwrap();
+    if !warned_formats.contains(&image_format) {
+        warned_formats.push(image_format);
+        eprintln!(
+            "mlaa_image: Metadata is not supported for {:?} files, dropping it",
+            image_format
+        );
+    }
+}
+
+pub fn encode_image(
+    image: DynamicImage,
+    image_format: ImageFormat,
+    metadata: &ImageMetadata,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    match image_format {
+        ImageFormat::Png => metadata.encode_png(&convert_for_format(image, image_format)),
+        ImageFormat::Tiff => metadata.encode_tiff(&convert_for_format(image, image_format)),
+        _ => {
+            warn_unsupported_metadata(metadata, image_format);
+            Ok(encode_dynamic_image(image, image_format)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GrayImage, Luma, Rgb, Rgb32FImage, RgbImage, Rgba, Rgba32FImage, RgbaImage};
+
+    use super::*;
+
+    fn gradient_rgba8() -> RgbaImage {
+        RgbaImage::from_fn(7, 5, |x, y| {
+            Rgba([(x * 36) as u8, (y * 60) as u8, 200, 255 - (x * y) as u8])
+        })
+    }
+
+    fn assert_round_trip(image: DynamicImage, image_format: ImageFormat) {
+        let image_data = encode_image(image.clone(), image_format, &ImageMetadata::default()).unwrap();
+        assert_eq!(detect_image_format(&image_data, None, None).unwrap(), image_format);
+        assert_eq!(
+            decode_image(&image_data, image_format).unwrap(),
+            image,
+            "{:?}",
+            image_format
+        );
+    }
+
+    #[test]
+    fn format_detection_precedence() {
+        let png_data = encode_image(gradient_rgba8().into(), ImageFormat::Png, &ImageMetadata::default()).unwrap();
+        let tga_data = encode_image(gradient_rgba8().into(), ImageFormat::Tga, &ImageMetadata::default()).unwrap();
+        let tga_path = Path::new("image.tga");
+
+        // An explicit --input-format wins over both the contents and the extension.
+        assert_eq!(
+            detect_image_format(&png_data, Some(ImageFormat::Bmp), Some(tga_path)).unwrap(),
+            ImageFormat::Bmp
+        );
+        // Sniffed contents win over the extension.
+        assert_eq!(
+            detect_image_format(&png_data, None, Some(tga_path)).unwrap(),
+            ImageFormat::Png
+        );
+        // TGA files have no signature, so their extension is used.
+        assert_eq!(
+            detect_image_format(&tga_data, None, Some(tga_path)).unwrap(),
+            ImageFormat::Tga
+        );
+        assert!(detect_image_format(&tga_data, None, None).is_err());
+    }
+
+    #[test]
+    fn tiff_round_trip() {
+        assert_round_trip(gradient_rgba8().into(), ImageFormat::Tiff);
+        assert_round_trip(
+            DynamicImage::from(gradient_rgba8()).into_rgb16().into(),
+            ImageFormat::Tiff,
+        );
+        assert_round_trip(
+            DynamicImage::from(gradient_rgba8()).into_luma8().into(),
+            ImageFormat::Tiff,
+        );
+    }
+
+    #[test]
+    fn webp_round_trip() {
+        assert_round_trip(gradient_rgba8().into(), ImageFormat::WebP);
+
+        // Lossless WebP images are always decoded as RGBA.
+        let image = DynamicImage::from(gradient_rgba8()).into_rgb8();
+        let image_data = encode_image(image.clone().into(), ImageFormat::WebP, &ImageMetadata::default()).unwrap();
+        let decoded_image = decode_image(&image_data, ImageFormat::WebP).unwrap();
+        assert_eq!(decoded_image.to_rgba8(), DynamicImage::from(image).to_rgba8());
+    }
+
+    #[test]
+    fn qoi_round_trip() {
+        assert_round_trip(gradient_rgba8().into(), ImageFormat::Qoi);
+        assert_round_trip(
+            DynamicImage::from(gradient_rgba8()).into_rgb8().into(),
+            ImageFormat::Qoi,
+        );
+    }
+
+    #[test]
+    fn pnm_round_trip() {
+        assert_round_trip(
+            GrayImage::from_fn(7, 5, |x, y| Luma([(x * y * 7) as u8])).into(),
+            ImageFormat::Pnm,
+        );
+        assert_round_trip(
+            RgbImage::from_fn(7, 5, |x, y| Rgb([x as u8, y as u8, 9])).into(),
+            ImageFormat::Pnm,
+        );
+    }
+
+    #[test]
+    fn pnm_16bit_round_trip() {
+        assert_round_trip(
+            DynamicImage::from(gradient_rgba8()).into_luma16().into(),
+            ImageFormat::Pnm,
+        );
+    }
+
+    // RGBA images are written as PAM files.
+    #[test]
+    fn pam_round_trip() {
+        assert_round_trip(gradient_rgba8().into(), ImageFormat::Pnm);
+        assert_round_trip(
+            DynamicImage::from(gradient_rgba8()).into_luma_alpha8().into(),
+            ImageFormat::Pnm,
+        );
+    }
+
+    #[test]
+    fn float_exr_round_trip() {
+        let rgb = Rgb32FImage::from_fn(7, 5, |x, y| Rgb([x as f32 * 1.5, y as f32 * 0.25, -0.5]));
+        let rgba = Rgba32FImage::from_fn(7, 5, |x, y| Rgba([x as f32 * 1.5, y as f32 * 0.25, 16.0, 0.5]));
+
+        assert_round_trip(rgb.into(), ImageFormat::OpenExr);
+        assert_round_trip(rgba.into(), ImageFormat::OpenExr);
+    }
+}
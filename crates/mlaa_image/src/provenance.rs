@@ -0,0 +1,140 @@
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use mlaa_impl::MlaaOptions;
+
+use crate::metadata::{ImageMetadata, TextEncoding, TextEntry};
+
+pub const PROVENANCE_KEYWORD: &str = "mlaa:provenance";
+
+#[derive(Serialize, Deserialize)]
+pub struct Provenance {
+    pub tool_version: String,
+    #[serde(default)]
+    pub config_paths: Vec<PathBuf>,
+    pub options: MlaaOptions,
+}
+
+impl Provenance {
+    pub fn new(mlaa_options: &MlaaOptions, config_paths: Vec<PathBuf>) -> Provenance {
+        Provenance {
+            tool_version: env!("CARGO_PKG_VERSION").to_owned(),
+            config_paths: config_paths
+                .into_iter()
+                .map(|config_path| fs::canonicalize(&config_path).unwrap_or(config_path))
+                .collect(),
+            options: mlaa_options.clone(),
+        }
+    }
+
+    pub fn find(metadata: &ImageMetadata) -> Result<Option<Provenance>, Box<dyn Error>> {
+        if let Some(entry) = metadata.text.iter().find(|entry| entry.keyword == PROVENANCE_KEYWORD) {
+            Ok(Some(toml::from_str(&entry.text)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Replaces any earlier provenance record in the metadata.
+    pub fn embed(&self, metadata: &mut ImageMetadata) -> Result<(), Box<dyn Error>> {
+        metadata.text.retain(|entry| entry.keyword != PROVENANCE_KEYWORD);
+        metadata.text.push(TextEntry {
+            keyword: PROVENANCE_KEYWORD.to_owned(),
+            text: self.to_toml()?,
+            encoding: TextEncoding::Utf8,
+        });
+        Ok(())
+    }
+
+    pub fn to_toml(&self) -> Result<String, Box<dyn Error>> {
+        Ok(toml::to_string(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+
+    use super::*;
+    use crate::pipeline::{encode_image, output_metadata};
+    use crate::{MlaaArgs, ProcessArgs};
+
+    fn process_args(args: &[&str]) -> ProcessArgs {
+        MlaaArgs::parse_from(["mlaa_image"].iter().chain(args)).process_args
+    }
+
+    fn processed_image(image_format: ImageFormat) -> Vec<u8> {
+        let mut metadata = ImageMetadata::default();
+        Provenance::new(&MlaaOptions::default(), Vec::new())
+            .embed(&mut metadata)
+            .unwrap();
+
+        let image = DynamicImage::from(RgbImage::from_pixel(4, 4, Rgb([1, 2, 3])));
+        encode_image(image, image_format, &metadata).unwrap()
+    }
+
+    #[test]
+    fn processed_images_are_refused() {
+        for image_format in [ImageFormat::Png, ImageFormat::Tiff] {
+            let image_data = processed_image(image_format);
+
+            let err = output_metadata(
+                &image_data,
+                image_format,
+                &MlaaOptions::default(),
+                Vec::new(),
+                &process_args(&[]),
+            )
+            .err()
+            .unwrap()
+            .to_string();
+            assert!(err.contains("already processed"), "{}", err);
+            assert!(err.contains("--reprocess"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn reprocess_replaces_the_provenance_record() {
+        let image_data = processed_image(ImageFormat::Png);
+        let mlaa_options = MlaaOptions::preset("soft").unwrap();
+
+        let metadata = output_metadata(
+            &image_data,
+            ImageFormat::Png,
+            &mlaa_options,
+            Vec::new(),
+            &process_args(&["--reprocess"]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            metadata
+                .text
+                .iter()
+                .filter(|entry| entry.keyword == PROVENANCE_KEYWORD)
+                .count(),
+            1
+        );
+        assert!(Provenance::find(&metadata).unwrap().unwrap().options == mlaa_options);
+    }
+
+    #[test]
+    fn unprocessed_images_are_accepted() {
+        let image = DynamicImage::from(RgbImage::from_pixel(4, 4, Rgb([1, 2, 3])));
+        let image_data = encode_image(image, ImageFormat::Png, &ImageMetadata::default()).unwrap();
+
+        let metadata = output_metadata(
+            &image_data,
+            ImageFormat::Png,
+            &MlaaOptions::default(),
+            Vec::new(),
+            &process_args(&[]),
+        )
+        .unwrap();
+        assert!(Provenance::find(&metadata).unwrap().is_some());
+    }
+}
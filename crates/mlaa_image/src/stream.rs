@@ -0,0 +1,294 @@
+use std::error::Error;
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::str::FromStr;
+
+use clap::{Args, ValueEnum};
+use image::{ImageBuffer, Luma, LumaA, Pixel, Rgb, Rgba};
+use mlaa_impl::{mlaa_image_buffer_in_place, mlaa_linear_image_buffer_in_place, Channel, MlaaOptions};
+
+use crate::config::{resolve_config, OptionArgs};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FrameSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for FrameSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_dimension = |dimension: Option<&str>| {
+            dimension
+                .and_then(|dimension| dimension.parse::<u32>().ok())
+                .filter(|&dimension| dimension > 0)
+                .ok_or_else(|| format!("invalid frame size \"{}\", expected WIDTHxHEIGHT", s))
+        };
+
+        let mut dimensions = s.splitn(2, 'x');
+        Ok(FrameSize {
+            width: parse_dimension(dimensions.next())?,
+            height: parse_dimension(dimensions.next())?,
+        })
+    }
+}
+
+// Named after the ffmpeg pixel formats, multi-byte samples are little-endian.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+#[clap(rename_all = "lower")]
+pub enum PixelFormat {
+    Gray,
+    Ya8,
+    Rgb24,
+    Rgba,
+    Gray16le,
+    Ya16le,
+    Rgb48le,
+    Rgba64le,
+    Grayf32le,
+    Rgbaf32le,
+}
+
+#[derive(Args)]
+pub struct StreamArgs {
+    /// Frame size in pixels, for example 1920x1080
+    #[clap(long = "size")]
+    size: FrameSize,
+
+    /// Raw pixel format of the frames, the same on the input and the output. Float formats hold linear light
+    #[clap(long = "pix-fmt", value_enum)]
+    pixel_format: PixelFormat,
+
+    #[clap(short = 'c', long = "config")]
+    config_path: Option<PathBuf>,
+
+    #[command(flatten)]
+    option_args: OptionArgs,
+}
+
+trait RawSample: Channel {
+    const SIZE: usize;
+
+    fn read_le(bytes: &[u8]) -> Self;
+    fn write_le(self, bytes: &mut [u8]);
+
+    // Float frames hold linear light, like in `mlaa_dynamic_image`.
+    fn mlaa_in_place<P: Pixel<Subpixel = Self>>(image: &mut ImageBuffer<P, Vec<Self>>, mlaa_options: &MlaaOptions) {
+        mlaa_image_buffer_in_place(image, mlaa_options);
+    }
+}
+
+impl RawSample for u8 {
+    const SIZE: usize = 1;
+
+    fn read_le(bytes: &[u8]) -> Self {
+        bytes[0]
+    }
+
+    fn write_le(self, bytes: &mut [u8]) {
+        bytes[0] = self;
+    }
+}
+
+impl RawSample for u16 {
+    const SIZE: usize = 2;
+
+    fn read_le(bytes: &[u8]) -> Self {
+        u16::from_le_bytes([bytes[0], bytes[1]])
+    }
+
+    fn write_le(self, bytes: &mut [u8]) {
+        bytes.copy_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl RawSample for f32 {
+    const SIZE: usize = 4;
+
+    fn read_le(bytes: &[u8]) -> Self {
+        f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn write_le(self, bytes: &mut [u8]) {
+        bytes.copy_from_slice(&self.to_le_bytes());
+    }
+
+    fn mlaa_in_place<P: Pixel<Subpixel = Self>>(image: &mut ImageBuffer<P, Vec<Self>>, mlaa_options: &MlaaOptions) {
+        mlaa_linear_image_buffer_in_place(image, mlaa_options);
+    }
+}
+
+// Fills the whole buffer, returns false when the input ended cleanly before
+// the first byte of a frame.
+fn read_frame(reader: &mut impl Read, frame_data: &mut [u8]) -> Result<bool, Box<dyn Error>> {
+    let mut filled = 0;
+
+    while filled < frame_data.len() {
+        match reader.read(&mut frame_data[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(format!("Truncated frame, got {} of {} bytes", filled, frame_data.len()).into()),
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(true)
+}
+
+// The frame and image buffers are allocated once and reused for every frame.
+fn stream_frames<P>(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    size: FrameSize,
+    mlaa_options: &MlaaOptions,
+) -> Result<usize, Box<dyn Error>>
+where
+    P: Pixel,
+    P::Subpixel: RawSample,
+{
+    let sample_size = <P::Subpixel as RawSample>::SIZE;
+    let frame_length = size.width as usize * size.height as usize * P::CHANNEL_COUNT as usize * sample_size;
+
+    let mut frame_data = vec![0u8; frame_length];
+    let mut image = ImageBuffer::<P, Vec<P::Subpixel>>::new(size.width, size.height);
+
+    let mut frame_count = 0;
+
+    while read_frame(reader, &mut frame_data)? {
+        for (sample, bytes) in image.iter_mut().zip(frame_data.chunks_exact(sample_size)) {
+            *sample = RawSample::read_le(bytes);
+        }
+
+        RawSample::mlaa_in_place(&mut image, mlaa_options);
+
+        for (sample, bytes) in image.iter().zip(frame_data.chunks_exact_mut(sample_size)) {
+            sample.write_le(bytes);
+        }
+
+        // The next filter in the pipeline may be waiting for this frame.
+        writer.write_all(&frame_data)?;
+        writer.flush()?;
+
+        frame_count += 1;
+    }
+
+    Ok(frame_count)
+}
+
+pub fn stream_command(args: StreamArgs) -> Result<ExitCode, Box<dyn Error>> {
+    // Stdout carries the frames, every message goes to stderr.
+    let resolved_config = resolve_config(None, args.config_path.as_deref(), &args.option_args)?;
+    if resolved_config.config_paths.is_empty() {
+        eprintln!("mlaa_image: Using default MLAA options");
+    }
+    for config_path in &resolved_config.config_paths {
+        eprintln!("mlaa_image: Using config file \"{}\"", config_path.display());
+    }
+    for warning in &resolved_config.warnings {
+        eprintln!("mlaa_image: {}", warning);
+    }
+
+    let (reader, writer) = (&mut io::stdin().lock(), &mut io::stdout().lock());
+    let mlaa_options = &resolved_config.options;
+    let frame_count = match args.pixel_format {
+        PixelFormat::Gray => stream_frames::<Luma<u8>>(reader, writer, args.size, mlaa_options),
+        PixelFormat::Ya8 => stream_frames::<LumaA<u8>>(reader, writer, args.size, mlaa_options),
+        PixelFormat::Rgb24 => stream_frames::<Rgb<u8>>(reader, writer, args.size, mlaa_options),
+        PixelFormat::Rgba => stream_frames::<Rgba<u8>>(reader, writer, args.size, mlaa_options),
+        PixelFormat::Gray16le => stream_frames::<Luma<u16>>(reader, writer, args.size, mlaa_options),
+        PixelFormat::Ya16le => stream_frames::<LumaA<u16>>(reader, writer, args.size, mlaa_options),
+        PixelFormat::Rgb48le => stream_frames::<Rgb<u16>>(reader, writer, args.size, mlaa_options),
+        PixelFormat::Rgba64le => stream_frames::<Rgba<u16>>(reader, writer, args.size, mlaa_options),
+        PixelFormat::Grayf32le => stream_frames::<Luma<f32>>(reader, writer, args.size, mlaa_options),
+        PixelFormat::Rgbaf32le => stream_frames::<Rgba<f32>>(reader, writer, args.size, mlaa_options),
+    }?;
+
+    eprintln!("mlaa_image: {} frames processed", frame_count);
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba32FImage, RgbaImage};
+    use mlaa_impl::{mlaa_image_buffer, mlaa_linear_image_buffer};
+
+    const SIZE: FrameSize = FrameSize { width: 8, height: 8 };
+
+    fn staircase(offset: u32) -> RgbaImage {
+        RgbaImage::from_fn(SIZE.width, SIZE.height, |x, y| {
+            let v = if x + y < offset { 0 } else { 255 };
+            Rgba([v, v, v, 255])
+        })
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        let frames = [staircase(4), staircase(8), staircase(11)];
+        let input = frames
+            .iter()
+            .flat_map(|frame| frame.as_raw().clone())
+            .collect::<Vec<_>>();
+        let expected = frames
+            .iter()
+            .flat_map(|frame| mlaa_image_buffer(frame, &MlaaOptions::default()).into_raw())
+            .collect::<Vec<_>>();
+
+        let mut output = Vec::new();
+        let frame_count =
+            stream_frames::<Rgba<u8>>(&mut input.as_slice(), &mut output, SIZE, &MlaaOptions::default()).unwrap();
+
+        assert_eq!(frame_count, 3);
+        assert!(output == expected);
+    }
+
+    #[test]
+    fn float_frames_are_linear() {
+        let frame = Rgba32FImage::from_fn(SIZE.width, SIZE.height, |x, y| {
+            let v = if x + y < 8 { 0.0 } else { 4.0 };
+            Rgba([v, v, v, 1.0])
+        });
+        let input = frame.iter().flat_map(|sample| sample.to_le_bytes()).collect::<Vec<_>>();
+        let expected = mlaa_linear_image_buffer(&frame, &MlaaOptions::default())
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        let mut output = Vec::new();
+        stream_frames::<Rgba<f32>>(&mut input.as_slice(), &mut output, SIZE, &MlaaOptions::default()).unwrap();
+        assert!(output == expected);
+    }
+
+    #[test]
+    fn input_ends_between_frames() {
+        let mut output = Vec::new();
+        let frame_count =
+            stream_frames::<Rgba<u8>>(&mut io::empty(), &mut output, SIZE, &MlaaOptions::default()).unwrap();
+        assert_eq!(frame_count, 0);
+        assert!(output.is_empty());
+
+        let input = [staircase(8).into_raw(), staircase(8).into_raw()].concat();
+        let frame_count =
+            stream_frames::<Rgba<u8>>(&mut input.as_slice(), &mut output, SIZE, &MlaaOptions::default()).unwrap();
+        assert_eq!(frame_count, 2);
+        assert_eq!(output.len(), input.len());
+    }
+
+    #[test]
+    fn truncated_frames_are_errors() {
+        let frame = staircase(8).into_raw();
+        let input = [frame.as_slice(), &frame[..frame.len() / 2]].concat();
+
+        let mut output = Vec::new();
+        let result = stream_frames::<Rgba<u8>>(&mut input.as_slice(), &mut output, SIZE, &MlaaOptions::default());
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            format!("Truncated frame, got {} of {} bytes", frame.len() / 2, frame.len())
+        );
+        assert_eq!(output.len(), frame.len());
+    }
+}
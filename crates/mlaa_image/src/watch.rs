@@ -0,0 +1,202 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use crate::batch::{collect_jobs, configure_thread_pool, run_batch, BatchJob};
+use crate::config::{config_sources, ConfigEnv, CONFIG_FILE_NAME};
+use crate::ProcessArgs;
+
+// Editors tend to save files in several steps, events arriving within this
+// period are handled together.
+const DEBOUNCE_PERIOD: Duration = Duration::from_millis(200);
+
+fn job_config_paths(job: &BatchJob, args: &ProcessArgs) -> Vec<PathBuf> {
+    config_sources(
+        &ConfigEnv::from_process(),
+        Some(&job.input_path),
+        args.config_path.as_deref(),
+    )
+    .iter()
+    .filter_map(|config_source| config_source.path())
+    .map(|config_path| fs::canonicalize(config_path).unwrap_or_else(|_| config_path.to_owned()))
+    .collect()
+}
+
+// Decides which jobs are processed again after a batch of debounced events,
+// `job_config_paths` lists the config files applying to a job.
+fn affected_jobs(
+    jobs: Vec<BatchJob>,
+    events: &[Event],
+    job_config_paths: impl Fn(&BatchJob) -> Vec<PathBuf>,
+) -> Vec<BatchJob> {
+    let changed_paths = events
+        .iter()
+        .filter(|event| {
+            matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            )
+        })
+        .flat_map(|event| event.paths.iter())
+        .collect::<HashSet<_>>();
+
+    // Created or removed config files may change which config applies to an
+    // input, so every input below them is affected.
+    let config_directories = changed_paths
+        .iter()
+        .filter(|path| path.file_name() == Some(CONFIG_FILE_NAME.as_ref()))
+        .filter_map(|path| path.parent())
+        .collect::<Vec<_>>();
+
+    // Outputs are never inputs of the same batch, so writing them doesn't
+    // trigger another run.
+    jobs.into_iter()
+        .filter(|job| {
+            changed_paths.contains(&job.input_path)
+                || config_directories
+                    .iter()
+                    .any(|config_directory| job.input_path.starts_with(config_directory))
+                || job_config_paths(job)
+                    .iter()
+                    .any(|config_path| changed_paths.contains(config_path))
+        })
+        .collect()
+}
+
+pub fn watch_command(directory: &Path, args: ProcessArgs) -> Result<ExitCode, Box<dyn Error>> {
+    if !args.input_paths.is_empty() {
+        return Err("The watched directory is the only input of the watch command, --input can't be used".into());
+    }
+
+    // Event paths are reported relative to the watched paths, the inputs are
+    // canonicalized the same way so they can be matched against them.
+    let directory = fs::canonicalize(directory)?;
+    let args = ProcessArgs {
+        input_paths: vec![directory.clone()],
+        config_path: args.config_path.map(fs::canonicalize).transpose()?,
+        ..args
+    };
+
+    configure_thread_pool(&args)?;
+
+    let (sender, receiver) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(sender)?;
+
+    if args.recursive {
+        watcher.watch(&directory, RecursiveMode::Recursive)?;
+    } else {
+        watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+    }
+
+    // Config files outside of the watched directory. Their directories are
+    // watched instead of the files, editors often replace the file on save.
+    let config_paths = collect_jobs(&args)?
+        .iter()
+        .flat_map(|job| job_config_paths(job, &args))
+        .filter(|config_path| !config_path.starts_with(&directory))
+        .collect::<HashSet<_>>();
+
+    for config_path in &config_paths {
+        if let Some(config_directory) = config_path.parent() {
+            watcher.watch(config_directory, RecursiveMode::NonRecursive)?;
+        }
+    }
+
+    run_batch(&collect_jobs(&args)?, &args)?;
+    eprintln!("mlaa_image: Watching \"{}\" for changes", directory.display());
+
+    loop {
+        let mut events = Vec::new();
+        let mut add_event = |event: notify::Result<Event>| match event {
+            Ok(event) => events.push(event),
+            Err(err) => eprintln!("mlaa_image: {}", err),
+        };
+
+        add_event(receiver.recv()?);
+        while let Ok(event) = receiver.recv_timeout(DEBOUNCE_PERIOD) {
+            add_event(event);
+        }
+
+        let jobs = match collect_jobs(&args) {
+            Ok(jobs) => jobs,
+            Err(err) => {
+                eprintln!("mlaa_image: {}", err);
+                continue;
+            }
+        };
+
+        let affected_jobs = affected_jobs(jobs, &events, |job| job_config_paths(job, &args));
+
+        if !affected_jobs.is_empty() {
+            run_batch(&affected_jobs, &args)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, ModifyKind};
+
+    fn job(input_path: &str, output_path: &str) -> BatchJob {
+        BatchJob {
+            input_path: PathBuf::from(input_path),
+            output_path: PathBuf::from(output_path),
+        }
+    }
+
+    fn input_paths(jobs: &[BatchJob]) -> Vec<&Path> {
+        jobs.iter().map(|job| job.input_path.as_path()).collect()
+    }
+
+    fn watched_jobs() -> Vec<BatchJob> {
+        vec![
+            job("/images/a.png", "/images/out/a.png"),
+            job("/images/sub/b.png", "/images/out/sub/b.png"),
+            job("/other/c.png", "/other/out/c.png"),
+        ]
+    }
+
+    #[test]
+    fn config_changes_reprocess_the_files_below_them() {
+        let events = [Event::new(EventKind::Create(CreateKind::File)).add_path(PathBuf::from("/images/sub/.mlaa"))];
+        let affected = affected_jobs(watched_jobs(), &events, |_| Vec::new());
+        assert_eq!(input_paths(&affected), [Path::new("/images/sub/b.png")]);
+
+        // Config files outside of the watched directory
+        let events = [Event::new(EventKind::Modify(ModifyKind::Any)).add_path(PathBuf::from("/config/mlaa.toml"))];
+        let affected = affected_jobs(watched_jobs(), &events, |job| {
+            if job.input_path.starts_with("/images") {
+                vec![PathBuf::from("/config/mlaa.toml")]
+            } else {
+                Vec::new()
+            }
+        });
+        assert_eq!(
+            input_paths(&affected),
+            [Path::new("/images/a.png"), Path::new("/images/sub/b.png")]
+        );
+    }
+
+    #[test]
+    fn output_writes_dont_retrigger_processing() {
+        let events = [
+            Event::new(EventKind::Create(CreateKind::File)).add_path(PathBuf::from("/images/out/a.png")),
+            Event::new(EventKind::Modify(ModifyKind::Any)).add_path(PathBuf::from("/images/out/sub/b.png")),
+        ];
+        assert!(affected_jobs(watched_jobs(), &events, |_| Vec::new()).is_empty());
+
+        let events = [
+            Event::new(EventKind::Modify(ModifyKind::Any)).add_path(PathBuf::from("/images/out/a.png")),
+            Event::new(EventKind::Modify(ModifyKind::Any)).add_path(PathBuf::from("/other/c.png")),
+        ];
+        let affected = affected_jobs(watched_jobs(), &events, |_| Vec::new());
+        assert_eq!(input_paths(&affected), [Path::new("/other/c.png")]);
+    }
+}
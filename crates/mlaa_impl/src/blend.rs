@@ -0,0 +1,185 @@
+use std::str::FromStr;
+
+use crate::color::{
+    cielab_to_linear, linear_to_cielab, linear_to_oklab, linear_to_srgb, oklab_to_linear, srgb_to_linear,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum BlendSpace {
+    Srgb,
+    #[default]
+    Linear,
+    Oklab,
+    Cielab,
+}
+
+impl BlendSpace {
+    pub const ALL: [BlendSpace; 4] = [
+        BlendSpace::Srgb,
+        BlendSpace::Linear,
+        BlendSpace::Oklab,
+        BlendSpace::Cielab,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BlendSpace::Srgb => "srgb",
+            BlendSpace::Linear => "linear",
+            BlendSpace::Oklab => "oklab",
+            BlendSpace::Cielab => "cielab",
+        }
+    }
+
+    // Human-readable name for user interfaces.
+    pub fn label(self) -> &'static str {
+        match self {
+            BlendSpace::Srgb => "sRGB",
+            BlendSpace::Linear => "Linear",
+            BlendSpace::Oklab => "OKLab",
+            BlendSpace::Cielab => "CIELab",
+        }
+    }
+
+    // Blends two non-premultiplied, sRGB-encoded RGBA colors with components
+    // in the 0.0..=1.0 range. Alpha is always interpolated linearly. The result
+    // isn't clamped, so HDR components above 1.0 are blended as well.
+    pub fn blend(self, c1: [f32; 4], c2: [f32; 4], t: f32) -> [f32; 4] {
+        fn lerp(a: f32, b: f32, t: f32) -> f32 {
+            a * (1.0 - t) + b * t
+        }
+
+        fn lerp3(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+            [lerp(a[0], b[0], t), lerp(a[1], b[1], t), lerp(a[2], b[2], t)]
+        }
+
+        fn decode(c: [f32; 4]) -> [f32; 3] {
+            [srgb_to_linear(c[0]), srgb_to_linear(c[1]), srgb_to_linear(c[2])]
+        }
+
+        fn encode(c: [f32; 3]) -> [f32; 3] {
+            [linear_to_srgb(c[0]), linear_to_srgb(c[1]), linear_to_srgb(c[2])]
+        }
+
+        let [r, g, b] = match self {
+            BlendSpace::Srgb => lerp3([c1[0], c1[1], c1[2]], [c2[0], c2[1], c2[2]], t),
+            BlendSpace::Linear => encode(lerp3(decode(c1), decode(c2), t)),
+            BlendSpace::Oklab => encode(oklab_to_linear(lerp3(
+                linear_to_oklab(decode(c1)),
+                linear_to_oklab(decode(c2)),
+                t,
+            ))),
+            BlendSpace::Cielab => encode(cielab_to_linear(lerp3(
+                linear_to_cielab(decode(c1)),
+                linear_to_cielab(decode(c2)),
+                t,
+            ))),
+        };
+
+        [r, g, b, lerp(c1[3], c2[3], t)]
+    }
+}
+
+impl FromStr for BlendSpace {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        BlendSpace::ALL
+            .into_iter()
+            .find(|value| value.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown blend space \"{}\", expected one of {}",
+                    name,
+                    BlendSpace::ALL.map(BlendSpace::name).join(", ")
+                )
+            })
+    }
+}
+
+#[cfg(feature = "clap")]
+impl clap::ValueEnum for BlendSpace {
+    fn value_variants<'a>() -> &'a [Self] {
+        &BlendSpace::ALL
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(clap::builder::PossibleValue::new(self.name()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: [f32; 4], expected: [f32; 4]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() <= 1e-4, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn endpoints_are_preserved() {
+        let c1 = [0.1, 0.5, 0.9, 1.0];
+        let c2 = [0.8, 0.3, 0.0, 0.5];
+
+        for blend_space in BlendSpace::ALL {
+            assert_close(blend_space.blend(c1, c2, 0.0), c1);
+            assert_close(blend_space.blend(c1, c2, 1.0), c2);
+        }
+    }
+
+    #[test]
+    fn equal_colors_are_unchanged() {
+        let c = [0.2, 0.4, 0.6, 0.8];
+
+        for blend_space in BlendSpace::ALL {
+            assert_close(blend_space.blend(c, c, 0.3), c);
+        }
+    }
+
+    #[test]
+    fn hdr_colors_are_not_clamped() {
+        let bright = [1.5, 2.0, 3.0, 1.0];
+        let black = [0.0, 0.0, 0.0, 1.0];
+
+        for blend_space in BlendSpace::ALL {
+            assert_close(blend_space.blend(bright, black, 0.0), bright);
+        }
+
+        assert_close(BlendSpace::Srgb.blend(bright, black, 0.5), [0.75, 1.0, 1.5, 1.0]);
+    }
+
+    #[test]
+    fn black_white_midpoints() {
+        let black = [0.0, 0.0, 0.0, 0.0];
+        let white = [1.0, 1.0, 1.0, 1.0];
+
+        assert_close(BlendSpace::Srgb.blend(black, white, 0.5), [0.5, 0.5, 0.5, 0.5]);
+        assert_close(
+            BlendSpace::Linear.blend(black, white, 0.5),
+            [0.735357, 0.735357, 0.735357, 0.5],
+        );
+        // Oklab lightness 0.5 on the gray axis is the linear luminance 0.125.
+        assert_close(
+            BlendSpace::Oklab.blend(black, white, 0.5),
+            [0.388572, 0.388572, 0.388572, 0.5],
+        );
+        // L* = 50 is the linear luminance 0.184187.
+        assert_close(
+            BlendSpace::Cielab.blend(black, white, 0.5),
+            [0.466340, 0.466340, 0.466340, 0.5],
+        );
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for value in BlendSpace::ALL {
+            assert_eq!(value.name().parse::<BlendSpace>(), Ok(value));
+        }
+
+        assert!("rgb".parse::<BlendSpace>().unwrap_err().contains("expected one of"));
+    }
+}
@@ -0,0 +1,177 @@
+use std::str::FromStr;
+
+use crate::color::{linear_to_cielab, srgb_to_linear};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum BrightnessMetric {
+    Rec601Luma,
+    #[default]
+    Rec709Luma,
+    LinearLuminance,
+    CieLightness,
+    MaxChannel,
+    AlphaWeightedLuma,
+}
+
+impl BrightnessMetric {
+    pub const ALL: [BrightnessMetric; 6] = [
+        BrightnessMetric::Rec601Luma,
+        BrightnessMetric::Rec709Luma,
+        BrightnessMetric::LinearLuminance,
+        BrightnessMetric::CieLightness,
+        BrightnessMetric::MaxChannel,
+        BrightnessMetric::AlphaWeightedLuma,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BrightnessMetric::Rec601Luma => "rec601_luma",
+            BrightnessMetric::Rec709Luma => "rec709_luma",
+            BrightnessMetric::LinearLuminance => "linear_luminance",
+            BrightnessMetric::CieLightness => "cie_lightness",
+            BrightnessMetric::MaxChannel => "max_channel",
+            BrightnessMetric::AlphaWeightedLuma => "alpha_weighted_luma",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BrightnessMetric::Rec601Luma => "Rec.601 luma",
+            BrightnessMetric::Rec709Luma => "Rec.709 luma",
+            BrightnessMetric::LinearLuminance => "Linear luminance",
+            BrightnessMetric::CieLightness => "CIE L*",
+            BrightnessMetric::MaxChannel => "Max channel",
+            BrightnessMetric::AlphaWeightedLuma => "Alpha-weighted luma",
+        }
+    }
+
+    // Measures the brightness of a non-premultiplied, sRGB-encoded RGBA color
+    // with components in the 0.0..=1.0 range.
+    pub fn brightness(self, c: [f32; 4]) -> f32 {
+        let [r, g, b, a] = c;
+
+        match self {
+            BrightnessMetric::Rec601Luma => 0.299 * r + 0.587 * g + 0.114 * b,
+            BrightnessMetric::Rec709Luma => 0.2126 * r + 0.7152 * g + 0.0722 * b,
+            BrightnessMetric::LinearLuminance => {
+                0.2126 * srgb_to_linear(r) + 0.7152 * srgb_to_linear(g) + 0.0722 * srgb_to_linear(b)
+            }
+            BrightnessMetric::CieLightness => {
+                linear_to_cielab([srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b)])[0]
+            }
+            BrightnessMetric::MaxChannel => r.max(g).max(b),
+            BrightnessMetric::AlphaWeightedLuma => (0.2126 * r + 0.7152 * g + 0.0722 * b) * a,
+        }
+    }
+}
+
+impl FromStr for BrightnessMetric {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        BrightnessMetric::ALL
+            .into_iter()
+            .find(|value| value.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown brightness metric \"{}\", expected one of {}",
+                    name,
+                    BrightnessMetric::ALL.map(BrightnessMetric::name).join(", ")
+                )
+            })
+    }
+}
+
+#[cfg(feature = "clap")]
+impl clap::ValueEnum for BrightnessMetric {
+    fn value_variants<'a>() -> &'a [Self] {
+        &BrightnessMetric::ALL
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(clap::builder::PossibleValue::new(self.name()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_brightness(metric: BrightnessMetric, c: [f32; 4], expected: f32) {
+        let actual = metric.brightness(c);
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{:?}: {} != {}",
+            metric,
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn known_values() {
+        let gray = [0.5, 0.5, 0.5, 1.0];
+        let color = [0.2, 0.6, 0.9, 0.5];
+
+        assert_brightness(BrightnessMetric::Rec601Luma, gray, 0.5);
+        assert_brightness(BrightnessMetric::Rec601Luma, color, 0.5146);
+
+        assert_brightness(BrightnessMetric::Rec709Luma, gray, 0.5);
+        assert_brightness(BrightnessMetric::Rec709Luma, color, 0.53662);
+
+        assert_brightness(BrightnessMetric::LinearLuminance, gray, 0.214041);
+        assert_brightness(BrightnessMetric::LinearLuminance, color, 0.291714);
+
+        assert_brightness(BrightnessMetric::CieLightness, gray, 53.38896);
+        assert_brightness(BrightnessMetric::CieLightness, color, 60.92973);
+
+        assert_brightness(BrightnessMetric::MaxChannel, gray, 0.5);
+        assert_brightness(BrightnessMetric::MaxChannel, color, 0.9);
+
+        assert_brightness(BrightnessMetric::AlphaWeightedLuma, gray, 0.5);
+        assert_brightness(BrightnessMetric::AlphaWeightedLuma, color, 0.26831);
+    }
+
+    #[test]
+    fn black_and_white() {
+        for metric in BrightnessMetric::ALL {
+            let white = if metric == BrightnessMetric::CieLightness {
+                100.0
+            } else {
+                1.0
+            };
+
+            assert_brightness(metric, [0.0, 0.0, 0.0, 1.0], 0.0);
+            assert_brightness(metric, [1.0, 1.0, 1.0, 1.0], white);
+        }
+    }
+
+    #[test]
+    fn only_alpha_weighted_luma_depends_on_alpha() {
+        for metric in BrightnessMetric::ALL {
+            let opaque = metric.brightness([0.3, 0.7, 0.1, 1.0]);
+            let transparent = metric.brightness([0.3, 0.7, 0.1, 0.0]);
+
+            if metric == BrightnessMetric::AlphaWeightedLuma {
+                assert_eq!(transparent, 0.0);
+            } else {
+                assert_eq!(opaque, transparent);
+            }
+        }
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for value in BrightnessMetric::ALL {
+            assert_eq!(value.name().parse::<BrightnessMetric>(), Ok(value));
+        }
+
+        assert!("luma"
+            .parse::<BrightnessMetric>()
+            .unwrap_err()
+            .contains("expected one of"));
+    }
+}
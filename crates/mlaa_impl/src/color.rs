@@ -0,0 +1,153 @@
+#![allow(clippy::excessive_precision)]
+
+// Conversions between sRGB-encoded, linear light, OKLab and CIELab (D65)
+// color spaces. All RGB values are normalized to the 0.0..=1.0 range.
+
+pub(crate) fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub(crate) fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub(crate) fn linear_to_oklab([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+pub(crate) fn oklab_to_linear([l, a, b]: [f32; 3]) -> [f32; 3] {
+    let l_ = (l + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m_ = (l - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s_ = (l - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+
+    [
+        4.0767416621 * l_ - 3.3077115913 * m_ + 0.2309699292 * s_,
+        -1.2684380046 * l_ + 2.6097574011 * m_ - 0.3413193965 * s_,
+        -0.0041960863 * l_ - 0.7034186147 * m_ + 1.7076147010 * s_,
+    ]
+}
+
+const D65_WHITE: [f32; 3] = [0.95047, 1.0, 1.08883];
+const CIELAB_DELTA: f32 = 6.0 / 29.0;
+
+pub(crate) fn linear_to_cielab([r, g, b]: [f32; 3]) -> [f32; 3] {
+    fn f(t: f32) -> f32 {
+        if t > CIELAB_DELTA.powi(3) {
+            t.cbrt()
+        } else {
+            t / (3.0 * CIELAB_DELTA.powi(2)) + 4.0 / 29.0
+        }
+    }
+
+    let x = 0.4124564 * r + 0.3575761 * g + 0.1804375 * b;
+    let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+    let z = 0.0193339 * r + 0.1191920 * g + 0.9503041 * b;
+
+    let (fx, fy, fz) = (f(x / D65_WHITE[0]), f(y / D65_WHITE[1]), f(z / D65_WHITE[2]));
+
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+pub(crate) fn cielab_to_linear([l, a, b]: [f32; 3]) -> [f32; 3] {
+    fn f_inv(t: f32) -> f32 {
+        if t > CIELAB_DELTA {
+            t.powi(3)
+        } else {
+            3.0 * CIELAB_DELTA.powi(2) * (t - 4.0 / 29.0)
+        }
+    }
+
+    let fy = (l + 16.0) / 116.0;
+    let fx = fy + a / 500.0;
+    let fz = fy - b / 200.0;
+
+    let (x, y, z) = (
+        f_inv(fx) * D65_WHITE[0],
+        f_inv(fy) * D65_WHITE[1],
+        f_inv(fz) * D65_WHITE[2],
+    );
+
+    [
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3], tolerance: f32) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() <= tolerance, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn srgb_known_values() {
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_to_linear(0.5) - 0.214041).abs() < 1e-5);
+        assert!((srgb_to_linear(0.04045) - 0.04045 / 12.92).abs() < 1e-7);
+
+        assert_eq!(linear_to_srgb(0.0), 0.0);
+        assert!((linear_to_srgb(1.0) - 1.0).abs() < 1e-6);
+        assert!((linear_to_srgb(0.214041) - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn srgb_round_trip() {
+        for i in 0..=255 {
+            let v = i as f32 / 255.0;
+            assert!((linear_to_srgb(srgb_to_linear(v)) - v).abs() < 1e-5, "{}", v);
+        }
+    }
+
+    #[test]
+    fn oklab_known_values() {
+        assert_close(linear_to_oklab([0.0, 0.0, 0.0]), [0.0, 0.0, 0.0], 1e-6);
+        assert_close(linear_to_oklab([1.0, 1.0, 1.0]), [1.0, 0.0, 0.0], 1e-4);
+        assert_close(linear_to_oklab([1.0, 0.0, 0.0]), [0.627955, 0.224863, 0.125846], 1e-4);
+        assert_close(linear_to_oklab([0.0, 1.0, 0.0]), [0.866440, -0.233888, 0.179498], 1e-4);
+        assert_close(linear_to_oklab([0.0, 0.0, 1.0]), [0.452014, -0.032457, -0.311528], 1e-4);
+    }
+
+    #[test]
+    fn cielab_known_values() {
+        assert_close(linear_to_cielab([0.0, 0.0, 0.0]), [0.0, 0.0, 0.0], 1e-4);
+        assert_close(linear_to_cielab([1.0, 1.0, 1.0]), [100.0, 0.0, 0.0], 1e-2);
+        assert_close(linear_to_cielab([1.0, 0.0, 0.0]), [53.2408, 80.0925, 67.2032], 1e-2);
+        assert_close(linear_to_cielab([0.0, 1.0, 0.0]), [87.7347, -86.1827, 83.1793], 1e-2);
+        assert_close(linear_to_cielab([0.0, 0.0, 1.0]), [32.2970, 79.1875, -107.8602], 1e-2);
+    }
+
+    #[test]
+    fn lab_round_trips() {
+        for r in 0..=4 {
+            for g in 0..=4 {
+                for b in 0..=4 {
+                    let c = [r as f32 / 4.0, g as f32 / 4.0, b as f32 / 4.0];
+                    assert_close(oklab_to_linear(linear_to_oklab(c)), c, 1e-4);
+                    assert_close(cielab_to_linear(linear_to_cielab(c)), c, 1e-4);
+                }
+            }
+        }
+    }
+}
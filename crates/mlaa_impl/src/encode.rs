@@ -0,0 +1,133 @@
+use std::io::Cursor;
+
+use image::codecs::pnm::PnmEncoder;
+use image::{ColorType, DynamicImage, ImageFormat, ImageResult};
+
+// Converts the image to the closest color type supported by the encoder of
+// the given format.
+pub fn convert_for_format(image: DynamicImage, image_format: ImageFormat) -> DynamicImage {
+    let color_type = image.color();
+
+    let to_8bit = |image: DynamicImage| -> DynamicImage {
+        match (color_type.has_color(), color_type.has_alpha()) {
+            (false, false) => image.into_luma8().into(),
+            (false, true) => image.into_luma_alpha8().into(),
+            (true, false) => image.into_rgb8().into(),
+            (true, true) => image.into_rgba8().into(),
+        }
+    };
+
+    match image_format {
+        ImageFormat::OpenExr => match color_type {
+            ColorType::Rgb32F | ColorType::Rgba32F => image,
+            _ if color_type.has_alpha() => image.into_rgba32f().into(),
+            _ => image.into_rgb32f().into(),
+        },
+        ImageFormat::Png => match color_type {
+            ColorType::Rgb32F => image.into_rgb16().into(),
+            ColorType::Rgba32F => image.into_rgba16().into(),
+            _ => image,
+        },
+        ImageFormat::Tiff => match color_type {
+            ColorType::La8 => image.into_rgba8().into(),
+            ColorType::La16 => image.into_rgba16().into(),
+            ColorType::Rgb32F => image.into_rgb16().into(),
+            ColorType::Rgba32F => image.into_rgba16().into(),
+            _ => image,
+        },
+        ImageFormat::Pnm => match color_type {
+            ColorType::L16 => image,
+            _ => to_8bit(image),
+        },
+        ImageFormat::Qoi if color_type.has_alpha() => image.into_rgba8().into(),
+        ImageFormat::Qoi => image.into_rgb8().into(),
+        _ => to_8bit(image),
+    }
+}
+
+// Writing through `DynamicImage::write_to` passes 16-bit samples to the PNM
+// encoder as bytes, which it rejects.
+fn encode_pnm(image: &DynamicImage) -> ImageResult<Vec<u8>> {
+    let mut image_data = Vec::new();
+    let mut encoder = PnmEncoder::new(&mut image_data);
+
+    if let Some(samples) = image.as_flat_samples_u16() {
+        encoder.encode(samples.samples, image.width(), image.height(), image.color())?;
+    } else {
+        encoder.encode(image.as_bytes(), image.width(), image.height(), image.color())?;
+    }
+
+    Ok(image_data)
+}
+
+// Encoders of formats that aren't enabled in the image crate return an
+// unsupported format error.
+pub fn encode_dynamic_image(image: DynamicImage, image_format: ImageFormat) -> ImageResult<Vec<u8>> {
+    let image = convert_for_format(image, image_format);
+
+    if image_format == ImageFormat::Pnm {
+        return encode_pnm(&image);
+    }
+
+    let mut image_data = Vec::new();
+    image.write_to(&mut Cursor::new(&mut image_data), image_format)?;
+    Ok(image_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GrayAlphaImage, ImageBuffer, LumaA, Rgb, Rgba32FImage};
+
+    use super::*;
+
+    #[test]
+    fn color_types_match_the_encoders() {
+        let float_image = DynamicImage::from(Rgba32FImage::new(2, 2));
+        let gray_alpha_image = DynamicImage::from(GrayAlphaImage::from_pixel(2, 2, LumaA([10, 20])));
+
+        let converted_color_type =
+            |image: &DynamicImage, image_format| convert_for_format(image.clone(), image_format).color();
+
+        assert_eq!(
+            converted_color_type(&float_image, ImageFormat::OpenExr),
+            ColorType::Rgba32F
+        );
+        assert_eq!(converted_color_type(&float_image, ImageFormat::Png), ColorType::Rgba16);
+        assert_eq!(converted_color_type(&float_image, ImageFormat::Tiff), ColorType::Rgba16);
+        assert_eq!(converted_color_type(&float_image, ImageFormat::WebP), ColorType::Rgba8);
+        assert_eq!(
+            converted_color_type(&gray_alpha_image, ImageFormat::Png),
+            ColorType::La8
+        );
+        assert_eq!(
+            converted_color_type(&gray_alpha_image, ImageFormat::Tiff),
+            ColorType::Rgba8
+        );
+        assert_eq!(
+            converted_color_type(&gray_alpha_image, ImageFormat::Pnm),
+            ColorType::La8
+        );
+        assert_eq!(
+            converted_color_type(&gray_alpha_image, ImageFormat::Qoi),
+            ColorType::Rgba8
+        );
+        assert_eq!(
+            converted_color_type(&gray_alpha_image, ImageFormat::Bmp),
+            ColorType::La8
+        );
+    }
+
+    #[test]
+    fn pnm_keeps_16bit_samples() {
+        let image = DynamicImage::from(ImageBuffer::from_fn(3, 2, |x, y| image::Luma([(x * 20000 + y) as u16])));
+
+        let image_data = encode_dynamic_image(image.clone(), ImageFormat::Pnm).unwrap();
+        let decoded_image = image::load_from_memory_with_format(&image_data, ImageFormat::Pnm).unwrap();
+        assert_eq!(decoded_image, image);
+
+        let rgb_image = DynamicImage::from(ImageBuffer::from_pixel(1, 1, Rgb([1.0f32, 0.5, 0.0])));
+        let image_data = encode_dynamic_image(rgb_image, ImageFormat::Pnm).unwrap();
+        let decoded_image = image::load_from_memory_with_format(&image_data, ImageFormat::Pnm).unwrap();
+        assert_eq!(decoded_image.color(), ColorType::Rgb8);
+    }
+}
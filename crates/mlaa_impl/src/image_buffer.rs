@@ -0,0 +1,417 @@
+use image::{DynamicImage, ImageBuffer, Pixel, Primitive};
+
+use crate::color::{linear_to_srgb, srgb_to_linear};
+use crate::{mlaa_features, mlaa_painter, MlaaFeature, MlaaOptions};
+
+pub trait Channel: Primitive {
+    fn to_unit(self) -> f32;
+    fn from_unit(v: f32) -> Self;
+}
+
+impl Channel for u8 {
+    fn to_unit(self) -> f32 {
+        self as f32 / u8::MAX as f32
+    }
+
+    fn from_unit(v: f32) -> Self {
+        (v.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8
+    }
+}
+
+impl Channel for u16 {
+    fn to_unit(self) -> f32 {
+        self as f32 / u16::MAX as f32
+    }
+
+    fn from_unit(v: f32) -> Self {
+        (v.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
+    }
+}
+
+impl Channel for f32 {
+    fn to_unit(self) -> f32 {
+        self
+    }
+
+    fn from_unit(v: f32) -> Self {
+        v
+    }
+}
+
+// Samples of a gray, gray and alpha, RGB or RGBA pixel, for bindings working
+// on raw sample buffers.
+pub fn samples_to_rgba<T: Channel>(samples: &[T]) -> [f32; 4] {
+    let v = |channel: usize| samples[channel].to_unit();
+
+    match samples.len() {
+        1 => [v(0), v(0), v(0), 1.0],
+        2 => [v(0), v(0), v(0), v(1)],
+        3 => [v(0), v(1), v(2), 1.0],
+        _ => [v(0), v(1), v(2), v(3)],
+    }
+}
+
+// Gray pixels get the Rec. 709 luma of the color.
+pub fn rgba_to_samples<T: Channel>(c: [f32; 4], samples: &mut [T]) {
+    let [r, g, b, a] = c;
+    let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+
+    let values: &[f32] = match samples.len() {
+        1 => &[luma],
+        2 => &[luma, a],
+        3 => &[r, g, b],
+        _ => &[r, g, b, a],
+    };
+
+    for (sample, &value) in samples.iter_mut().zip(values) {
+        *sample = T::from_unit(value);
+    }
+}
+
+pub fn pixel_to_rgba<P>(pixel: &P) -> [f32; 4]
+where
+    P: Pixel,
+    P::Subpixel: Channel,
+{
+    samples_to_rgba(pixel.channels())
+}
+
+pub fn rgba_to_pixel<P>(c: [f32; 4]) -> P
+where
+    P: Pixel,
+    P::Subpixel: Channel,
+{
+    let mut samples = [P::Subpixel::DEFAULT_MIN_VALUE; 4];
+    rgba_to_samples(c, &mut samples[..P::CHANNEL_COUNT as usize]);
+    *P::from_slice(&samples[..P::CHANNEL_COUNT as usize])
+}
+
+// Images which aren't `ImageBuffer`s, like the strided sample buffers of the
+// bindings, are processed through these traits.
+pub trait MlaaPixels {
+    fn dimensions(&self) -> (usize, usize);
+    fn get_rgba(&self, x: usize, y: usize) -> [f32; 4];
+}
+
+pub trait MlaaPixelsMut: MlaaPixels {
+    fn put_rgba(&mut self, x: usize, y: usize, c: [f32; 4]);
+}
+
+impl<P> MlaaPixels for ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    P::Subpixel: Channel,
+{
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width() as usize, self.height() as usize)
+    }
+
+    fn get_rgba(&self, x: usize, y: usize) -> [f32; 4] {
+        pixel_to_rgba(self.get_pixel(x as u32, y as u32))
+    }
+}
+
+impl<P> MlaaPixelsMut for ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    P::Subpixel: Channel,
+{
+    fn put_rgba(&mut self, x: usize, y: usize, c: [f32; 4]) {
+        self.put_pixel(x as u32, y as u32, rgba_to_pixel(c));
+    }
+}
+
+pub fn mlaa_image_buffer<P>(
+    input_image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    mlaa_options: &MlaaOptions,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    P::Subpixel: Channel,
+{
+    let mut output_image = input_image.clone();
+    mlaa_image_buffer_in_place(&mut output_image, mlaa_options);
+    output_image
+}
+
+// Keeps the pixel type of the input image, color types that aren't supported
+// by `Channel` are processed as 32-bit float RGBA. Float images are treated as
+// linear light.
+pub fn mlaa_dynamic_image(input_image: &DynamicImage, mlaa_options: &MlaaOptions) -> DynamicImage {
+    match input_image {
+        DynamicImage::ImageLuma8(image) => mlaa_image_buffer(image, mlaa_options).into(),
+        DynamicImage::ImageLumaA8(image) => mlaa_image_buffer(image, mlaa_options).into(),
+        DynamicImage::ImageRgb8(image) => mlaa_image_buffer(image, mlaa_options).into(),
+        DynamicImage::ImageRgba8(image) => mlaa_image_buffer(image, mlaa_options).into(),
+        DynamicImage::ImageLuma16(image) => mlaa_image_buffer(image, mlaa_options).into(),
+        DynamicImage::ImageLumaA16(image) => mlaa_image_buffer(image, mlaa_options).into(),
+        DynamicImage::ImageRgb16(image) => mlaa_image_buffer(image, mlaa_options).into(),
+        DynamicImage::ImageRgba16(image) => mlaa_image_buffer(image, mlaa_options).into(),
+        DynamicImage::ImageRgb32F(image) => mlaa_linear_image_buffer(image, mlaa_options).into(),
+        DynamicImage::ImageRgba32F(image) => mlaa_linear_image_buffer(image, mlaa_options).into(),
+        image => mlaa_image_buffer(&image.to_rgba32f(), mlaa_options).into(),
+    }
+}
+
+// The features `mlaa_dynamic_image` paints, with the same color handling.
+pub fn mlaa_dynamic_image_features(
+    input_image: &DynamicImage,
+    mlaa_options: &MlaaOptions,
+) -> Vec<MlaaFeature<[f32; 4]>> {
+    match input_image {
+        DynamicImage::ImageLuma8(image) => mlaa_pixels_features(image, mlaa_options),
+        DynamicImage::ImageLumaA8(image) => mlaa_pixels_features(image, mlaa_options),
+        DynamicImage::ImageRgb8(image) => mlaa_pixels_features(image, mlaa_options),
+        DynamicImage::ImageRgba8(image) => mlaa_pixels_features(image, mlaa_options),
+        DynamicImage::ImageLuma16(image) => mlaa_pixels_features(image, mlaa_options),
+        DynamicImage::ImageLumaA16(image) => mlaa_pixels_features(image, mlaa_options),
+        DynamicImage::ImageRgb16(image) => mlaa_pixels_features(image, mlaa_options),
+        DynamicImage::ImageRgba16(image) => mlaa_pixels_features(image, mlaa_options),
+        DynamicImage::ImageRgb32F(image) => mlaa_linear_pixels_features(image, mlaa_options),
+        DynamicImage::ImageRgba32F(image) => mlaa_linear_pixels_features(image, mlaa_options),
+        image => mlaa_pixels_features(&image.to_rgba32f(), mlaa_options),
+    }
+}
+
+pub fn mlaa_image_buffer_in_place<P>(image: &mut ImageBuffer<P, Vec<P::Subpixel>>, mlaa_options: &MlaaOptions)
+where
+    P: Pixel,
+    P::Subpixel: Channel,
+{
+    mlaa_pixels_in_place(image, mlaa_options);
+}
+
+pub fn mlaa_linear_image_buffer<P>(
+    input_image: &ImageBuffer<P, Vec<f32>>,
+    mlaa_options: &MlaaOptions,
+) -> ImageBuffer<P, Vec<f32>>
+where
+    P: Pixel<Subpixel = f32>,
+{
+    let mut output_image = input_image.clone();
+    mlaa_linear_image_buffer_in_place(&mut output_image, mlaa_options);
+    output_image
+}
+
+pub fn mlaa_linear_image_buffer_in_place<P>(image: &mut ImageBuffer<P, Vec<f32>>, mlaa_options: &MlaaOptions)
+where
+    P: Pixel<Subpixel = f32>,
+{
+    mlaa_linear_pixels_in_place(image, mlaa_options);
+}
+
+// Float images hold linear light, like the ones decoded from OpenEXR files.
+// Their colors are sRGB-encoded for the antialiasing without clamping them to
+// the 0.0..=1.0 range, so HDR values survive.
+fn linear_to_srgb_rgba([r, g, b, a]: [f32; 4]) -> [f32; 4] {
+    [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), a]
+}
+
+fn srgb_to_linear_rgba([r, g, b, a]: [f32; 4]) -> [f32; 4] {
+    [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+}
+
+fn map_colors(mlaa_feature: MlaaFeature<[f32; 4]>, f: impl Fn([f32; 4]) -> [f32; 4]) -> MlaaFeature<[f32; 4]> {
+    match mlaa_feature {
+        MlaaFeature::VerticalGradient { x, y, height, colors } => MlaaFeature::VerticalGradient {
+            x,
+            y,
+            height,
+            colors: (f(colors.0), f(colors.1)),
+        },
+        MlaaFeature::HorizontalGradient { x, y, width, colors } => MlaaFeature::HorizontalGradient {
+            x,
+            y,
+            width,
+            colors: (f(colors.0), f(colors.1)),
+        },
+        MlaaFeature::Corner { x, y, colors } => MlaaFeature::Corner {
+            x,
+            y,
+            colors: (f(colors.0), f(colors.1)),
+        },
+    }
+}
+
+// Colors of the returned features are in the encoding of the image.
+pub fn mlaa_pixels_features(image: &impl MlaaPixels, mlaa_options: &MlaaOptions) -> Vec<MlaaFeature<[f32; 4]>> {
+    collect_features(image, mlaa_options, |c| c)
+}
+
+pub fn mlaa_linear_pixels_features(image: &impl MlaaPixels, mlaa_options: &MlaaOptions) -> Vec<MlaaFeature<[f32; 4]>> {
+    collect_features(image, mlaa_options, linear_to_srgb_rgba)
+        .into_iter()
+        .map(|mlaa_feature| map_colors(mlaa_feature, srgb_to_linear_rgba))
+        .collect()
+}
+
+pub fn mlaa_pixels_in_place(image: &mut impl MlaaPixelsMut, mlaa_options: &MlaaOptions) {
+    process_in_place(image, mlaa_options, |c| c, |c| c);
+}
+
+pub fn mlaa_linear_pixels_in_place(image: &mut impl MlaaPixelsMut, mlaa_options: &MlaaOptions) {
+    process_in_place(image, mlaa_options, linear_to_srgb_rgba, srgb_to_linear_rgba);
+}
+
+fn collect_features(
+    image: &impl MlaaPixels,
+    mlaa_options: &MlaaOptions,
+    decode: impl Fn([f32; 4]) -> [f32; 4],
+) -> Vec<MlaaFeature<[f32; 4]>> {
+    let (width, height) = image.dimensions();
+    let mut mlaa_features_found = Vec::new();
+
+    mlaa_features(
+        width,
+        height,
+        |x, y| {
+            if (x < 0) || (y < 0) || (x as usize >= width) || (y as usize >= height) {
+                [0.0; 4]
+            } else {
+                decode(image.get_rgba(x as usize, y as usize))
+            }
+        },
+        |c| mlaa_options.brightness_metric.brightness(c),
+        mlaa_options,
+        |mlaa_feature| mlaa_features_found.push(mlaa_feature),
+    );
+
+    mlaa_features_found
+}
+
+// The features are collected before painting any of them, so the detection
+// only sees the original pixels.
+fn process_in_place(
+    image: &mut impl MlaaPixelsMut,
+    mlaa_options: &MlaaOptions,
+    decode: impl Fn([f32; 4]) -> [f32; 4],
+    encode: impl Fn([f32; 4]) -> [f32; 4],
+) {
+    let (width, height) = image.dimensions();
+
+    for mlaa_feature in &collect_features(image, mlaa_options, decode) {
+        mlaa_painter(
+            |c1, c2, t| mlaa_options.blend_space.blend(c1, c2, t),
+            |x, y, c| {
+                if (x >= 0) && (y >= 0) && ((x as usize) < width) && ((y as usize) < height) {
+                    image.put_rgba(x as usize, y as usize, encode(c));
+                }
+            },
+            mlaa_feature,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GrayAlphaImage, GrayImage, Luma, LumaA, Rgb, Rgb32FImage, RgbImage, Rgba, Rgba32FImage, RgbaImage};
+
+    use super::*;
+
+    fn assert_pixel_round_trip<P>(pixels: &[P])
+    where
+        P: Pixel + PartialEq + std::fmt::Debug,
+        P::Subpixel: Channel,
+    {
+        for pixel in pixels {
+            assert_eq!(rgba_to_pixel::<P>(pixel_to_rgba(pixel)), *pixel);
+        }
+    }
+
+    #[test]
+    fn pixel_round_trips() {
+        assert_pixel_round_trip(&(0..=255).map(|v| Luma([v as u8])).collect::<Vec<_>>());
+        assert_pixel_round_trip(&(0..=255).map(|v| LumaA([v as u8, 255 - v as u8])).collect::<Vec<_>>());
+        assert_pixel_round_trip(&[Rgb([0u8, 127, 255]), Rgb([13, 200, 77])]);
+        assert_pixel_round_trip(&[Rgba([0u8, 127, 255, 64]), Rgba([13, 200, 77, 0])]);
+        assert_pixel_round_trip(&(0..=u16::MAX).step_by(257).map(|v| Luma([v])).collect::<Vec<_>>());
+        assert_pixel_round_trip(&[LumaA([0u16, 65535]), LumaA([12345, 54321])]);
+        assert_pixel_round_trip(&[Rgb([0u16, 32768, 65535]), Rgb([1, 2, 3])]);
+        assert_pixel_round_trip(&[Rgba([0u16, 32768, 65535, 1000]), Rgba([1, 2, 3, 4])]);
+        assert_pixel_round_trip(&[Rgb([0.0f32, 0.25, 1.0]), Rgb([0.1, 0.2, 0.3])]);
+        assert_pixel_round_trip(&[Rgba([0.0f32, 0.25, 1.0, 0.5]), Rgba([0.1, 0.2, 0.3, 0.4])]);
+    }
+
+    #[test]
+    fn gray_samples_get_rec709_luma() {
+        let mut gray = [0u8];
+        rgba_to_samples([1.0, 0.0, 0.0, 1.0], &mut gray);
+        assert_eq!(gray, [54]);
+
+        let mut gray_alpha = [0u16; 2];
+        rgba_to_samples([0.0, 1.0, 0.0, 0.5], &mut gray_alpha);
+        assert_eq!(gray_alpha, [46871, 32768]);
+
+        assert_eq!(samples_to_rgba(&[51u8, 102]), [0.2, 0.2, 0.2, 0.4]);
+    }
+
+    // A uniform image has no edges, so every pixel type must come back
+    // unchanged.
+    #[test]
+    fn uniform_images_are_unchanged() {
+        let images: Vec<DynamicImage> = vec![
+            GrayImage::from_pixel(5, 4, Luma([100])).into(),
+            GrayAlphaImage::from_pixel(5, 4, LumaA([100, 200])).into(),
+            RgbImage::from_pixel(5, 4, Rgb([10, 20, 30])).into(),
+            RgbaImage::from_pixel(5, 4, Rgba([10, 20, 30, 40])).into(),
+            ImageBuffer::<Luma<u16>, _>::from_pixel(5, 4, Luma([40000])).into(),
+            ImageBuffer::<LumaA<u16>, _>::from_pixel(5, 4, LumaA([40000, 1234])).into(),
+            ImageBuffer::<Rgb<u16>, _>::from_pixel(5, 4, Rgb([1000, 2000, 3000])).into(),
+            ImageBuffer::<Rgba<u16>, _>::from_pixel(5, 4, Rgba([1000, 2000, 3000, 4000])).into(),
+            Rgb32FImage::from_pixel(5, 4, Rgb([0.1, 0.2, 0.3])).into(),
+            Rgba32FImage::from_pixel(5, 4, Rgba([0.1, 0.2, 0.3, 0.4])).into(),
+        ];
+
+        for image in &images {
+            assert_eq!(&mlaa_dynamic_image(image, &MlaaOptions::default()), image);
+        }
+    }
+
+    // With linear blending the antialiased pixels of an HDR edge are linear
+    // interpolations of its colors, and aren't clamped.
+    #[test]
+    fn float_images_are_linear_and_unclamped() {
+        let mut staircase = Rgb32FImage::from_pixel(8, 8, Rgb([4.0, 4.0, 4.0]));
+        for y in 0..8 {
+            for x in 0..y {
+                staircase.put_pixel(x, y, Rgb([0.0, 0.0, 0.0]));
+            }
+        }
+
+        let output_image = mlaa_dynamic_image(&staircase.into(), &MlaaOptions::default()).into_rgb32f();
+        let mut blended_pixel_count = 0;
+
+        for Rgb([r, g, b]) in output_image.pixels() {
+            assert!((r == g) && (g == b));
+            assert!((-1e-4..=4.0 + 1e-4).contains(r), "{}", r);
+
+            if (*r > 1.0) && (*r < 4.0 - 1e-3) {
+                blended_pixel_count += 1;
+            }
+        }
+
+        assert!(blended_pixel_count > 0);
+    }
+
+    #[test]
+    fn pixel_type_is_kept() {
+        let mut staircase = RgbImage::from_pixel(8, 8, Rgb([255, 255, 255]));
+        for y in 0..8 {
+            for x in 0..y {
+                staircase.put_pixel(x, y, Rgb([0, 0, 0]));
+            }
+        }
+        let staircase = DynamicImage::from(staircase);
+
+        for image in [
+            staircase.clone(),
+            staircase.to_luma8().into(),
+            staircase.to_rgba16().into(),
+            staircase.to_rgba32f().into(),
+        ] {
+            let output_image = mlaa_dynamic_image(&image, &MlaaOptions::default());
+            assert_eq!(output_image.color(), image.color());
+            assert_ne!(output_image, image);
+        }
+    }
+}
@@ -1,12 +1,41 @@
+mod blend;
+mod brightness;
+mod color;
+#[cfg(feature = "image")]
+mod encode;
+#[cfg(feature = "image")]
+mod image_buffer;
+mod preset;
+
+pub use crate::blend::BlendSpace;
+pub use crate::brightness::BrightnessMetric;
+#[cfg(feature = "image")]
+pub use crate::encode::{convert_for_format, encode_dynamic_image};
+#[cfg(feature = "image")]
+pub use crate::image_buffer::{
+    mlaa_dynamic_image, mlaa_dynamic_image_features, mlaa_image_buffer, mlaa_image_buffer_in_place,
+    mlaa_linear_image_buffer, mlaa_linear_image_buffer_in_place, mlaa_linear_pixels_features,
+    mlaa_linear_pixels_in_place, mlaa_pixels_features, mlaa_pixels_in_place, pixel_to_rgba, rgba_to_pixel,
+    rgba_to_samples, samples_to_rgba, Channel, MlaaPixels, MlaaPixelsMut,
+};
+
+#[derive(Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct MlaaOptions {
     pub vertical_smoothing: bool,
     pub horizontal_smoothing: bool,
     pub corner_smoothing: bool,
 
     pub strict_mode: bool,
+    #[cfg_attr(feature = "schemars", schemars(range(min = 0.0, max = 1.0)))]
     pub seam_split_position: f32,
+    #[cfg_attr(feature = "serde", serde(alias = "seam_brightness_balance"))]
     pub seam_brigtness_balance: bool,
+
+    pub blend_space: BlendSpace,
+    pub brightness_metric: BrightnessMetric,
 }
 
 impl Default for MlaaOptions {
@@ -19,10 +48,26 @@ impl Default for MlaaOptions {
             strict_mode: true,
             seam_split_position: 0.0,
             seam_brigtness_balance: false,
+
+            blend_space: BlendSpace::default(),
+            brightness_metric: BrightnessMetric::default(),
         }
     }
 }
 
+impl MlaaOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.seam_split_position) {
+            return Err(format!(
+                "`seam_split_position` must be within 0.0..=1.0, got {}",
+                self.seam_split_position
+            ));
+        }
+
+        Ok(())
+    }
+}
+
 pub enum MlaaFeature<C> {
     VerticalGradient {
         x: f32,
@@ -398,9 +443,9 @@ pub fn mlaa_features<B, C>(
     }
 }
 
-pub fn mlaa_painter<C>(
-    blend_colors: impl Fn(C, C, f32) -> C,
-    mut draw_pixel: impl FnMut(isize, isize, C),
+pub fn mlaa_painter<C, D>(
+    blend_colors: impl Fn(C, C, f32) -> D,
+    mut draw_pixel: impl FnMut(isize, isize, D),
     mlaa_feature: &MlaaFeature<C>,
 ) where
     C: PartialEq + Copy + Clone,
@@ -0,0 +1,128 @@
+use crate::{BlendSpace, MlaaOptions};
+
+impl MlaaOptions {
+    pub const PRESET_NAMES: [&'static str; 4] = ["olm-like", "strict-lineart", "soft", "pixel-art"];
+
+    // Returns `None` for unknown preset names.
+    pub fn preset(name: &str) -> Option<MlaaOptions> {
+        match name {
+            // Close to the defaults of OLM Smoother: loose seam matching,
+            // seams split in the middle and blending in sRGB space.
+            "olm-like" => Some(MlaaOptions {
+                strict_mode: false,
+                seam_split_position: 0.5,
+                seam_brigtness_balance: true,
+                blend_space: BlendSpace::Srgb,
+                ..MlaaOptions::default()
+            }),
+            // Only smooths clean two-color edges, for line art layers where
+            // nearby colors must not bleed into each other.
+            "strict-lineart" => Some(MlaaOptions {
+                strict_mode: true,
+                seam_split_position: 0.0,
+                seam_brigtness_balance: true,
+                ..MlaaOptions::default()
+            }),
+            // Loose seam matching and perceptual blending, for color fills.
+            "soft" => Some(MlaaOptions {
+                strict_mode: false,
+                seam_split_position: 0.5,
+                seam_brigtness_balance: false,
+                blend_space: BlendSpace::Oklab,
+                ..MlaaOptions::default()
+            }),
+            // Keeps single-pixel corners intact, which often carry detail in
+            // pixel art.
+            "pixel-art" => Some(MlaaOptions {
+                corner_smoothing: false,
+                strict_mode: true,
+                seam_split_position: 0.0,
+                seam_brigtness_balance: false,
+                blend_space: BlendSpace::Srgb,
+                ..MlaaOptions::default()
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BlendSpace, BrightnessMetric, MlaaOptions};
+
+    #[test]
+    fn presets_resolve_to_documented_options() {
+        let expected_presets = [
+            (
+                "olm-like",
+                MlaaOptions {
+                    vertical_smoothing: true,
+                    horizontal_smoothing: true,
+                    corner_smoothing: true,
+                    strict_mode: false,
+                    seam_split_position: 0.5,
+                    seam_brigtness_balance: true,
+                    blend_space: BlendSpace::Srgb,
+                    brightness_metric: BrightnessMetric::Rec709Luma,
+                },
+            ),
+            (
+                "strict-lineart",
+                MlaaOptions {
+                    vertical_smoothing: true,
+                    horizontal_smoothing: true,
+                    corner_smoothing: true,
+                    strict_mode: true,
+                    seam_split_position: 0.0,
+                    seam_brigtness_balance: true,
+                    blend_space: BlendSpace::Linear,
+                    brightness_metric: BrightnessMetric::Rec709Luma,
+                },
+            ),
+            (
+                "soft",
+                MlaaOptions {
+                    vertical_smoothing: true,
+                    horizontal_smoothing: true,
+                    corner_smoothing: true,
+                    strict_mode: false,
+                    seam_split_position: 0.5,
+                    seam_brigtness_balance: false,
+                    blend_space: BlendSpace::Oklab,
+                    brightness_metric: BrightnessMetric::Rec709Luma,
+                },
+            ),
+            (
+                "pixel-art",
+                MlaaOptions {
+                    vertical_smoothing: true,
+                    horizontal_smoothing: true,
+                    corner_smoothing: false,
+                    strict_mode: true,
+                    seam_split_position: 0.0,
+                    seam_brigtness_balance: false,
+                    blend_space: BlendSpace::Srgb,
+                    brightness_metric: BrightnessMetric::Rec709Luma,
+                },
+            ),
+        ];
+
+        assert_eq!(
+            expected_presets.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+            MlaaOptions::PRESET_NAMES
+        );
+
+        for (name, expected_options) in expected_presets {
+            let options = MlaaOptions::preset(name).unwrap();
+            assert!(options == expected_options, "preset \"{}\"", name);
+            assert_eq!(options.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn unknown_presets_are_rejected() {
+        assert!(MlaaOptions::preset("").is_none());
+        assert!(MlaaOptions::preset("Soft").is_none());
+        assert!(MlaaOptions::preset("pixel_art").is_none());
+    }
+}
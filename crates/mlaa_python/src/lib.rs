@@ -0,0 +1,444 @@
+use mlaa_impl::{
+    mlaa_linear_pixels_features, mlaa_linear_pixels_in_place, mlaa_pixels_features, mlaa_pixels_in_place,
+    rgba_to_samples, samples_to_rgba, BlendSpace, BrightnessMetric, Channel, MlaaFeature as ImplFeature,
+    MlaaOptions as ImplOptions, MlaaPixels, MlaaPixelsMut,
+};
+use numpy::ndarray::{Array3, ArrayBase, ArrayView3, ArrayViewMut3, Data, DataMut, Ix3};
+use numpy::{Element, PyArray3, PyReadonlyArray3, PyReadwriteArray3, PyUntypedArrayMethods};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+fn parse_blend_space(name: &str) -> PyResult<BlendSpace> {
+    name.parse().map_err(PyValueError::new_err)
+}
+
+fn parse_brightness_metric(name: &str) -> PyResult<BrightnessMetric> {
+    name.parse().map_err(PyValueError::new_err)
+}
+
+fn python_bool(v: bool) -> &'static str {
+    if v {
+        "True"
+    } else {
+        "False"
+    }
+}
+
+#[pyclass(eq, module = "mlaa")]
+#[derive(Clone, PartialEq)]
+pub struct MlaaOptions {
+    #[pyo3(get, set)]
+    vertical_smoothing: bool,
+    #[pyo3(get, set)]
+    horizontal_smoothing: bool,
+    #[pyo3(get, set)]
+    corner_smoothing: bool,
+
+    #[pyo3(get, set)]
+    strict_mode: bool,
+    #[pyo3(get, set)]
+    seam_split_position: f32,
+    #[pyo3(get, set)]
+    seam_brightness_balance: bool,
+
+    blend_space: BlendSpace,
+    brightness_metric: BrightnessMetric,
+}
+
+impl From<&ImplOptions> for MlaaOptions {
+    fn from(options: &ImplOptions) -> Self {
+        MlaaOptions {
+            vertical_smoothing: options.vertical_smoothing,
+            horizontal_smoothing: options.horizontal_smoothing,
+            corner_smoothing: options.corner_smoothing,
+
+            strict_mode: options.strict_mode,
+            seam_split_position: options.seam_split_position,
+            seam_brightness_balance: options.seam_brigtness_balance,
+
+            blend_space: options.blend_space,
+            brightness_metric: options.brightness_metric,
+        }
+    }
+}
+
+impl From<&MlaaOptions> for ImplOptions {
+    fn from(options: &MlaaOptions) -> Self {
+        ImplOptions {
+            vertical_smoothing: options.vertical_smoothing,
+            horizontal_smoothing: options.horizontal_smoothing,
+            corner_smoothing: options.corner_smoothing,
+
+            strict_mode: options.strict_mode,
+            seam_split_position: options.seam_split_position,
+            seam_brigtness_balance: options.seam_brightness_balance,
+
+            blend_space: options.blend_space,
+            brightness_metric: options.brightness_metric,
+        }
+    }
+}
+
+#[pymethods]
+impl MlaaOptions {
+    // Every option is keyword-only and falls back to the default options.
+    #[new]
+    #[pyo3(signature = (
+        *,
+        vertical_smoothing = None,
+        horizontal_smoothing = None,
+        corner_smoothing = None,
+        strict_mode = None,
+        seam_split_position = None,
+        seam_brightness_balance = None,
+        blend_space = None,
+        brightness_metric = None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        vertical_smoothing: Option<bool>,
+        horizontal_smoothing: Option<bool>,
+        corner_smoothing: Option<bool>,
+        strict_mode: Option<bool>,
+        seam_split_position: Option<f32>,
+        seam_brightness_balance: Option<bool>,
+        blend_space: Option<&str>,
+        brightness_metric: Option<&str>,
+    ) -> PyResult<Self> {
+        let defaults = MlaaOptions::from(&ImplOptions::default());
+
+        Ok(MlaaOptions {
+            vertical_smoothing: vertical_smoothing.unwrap_or(defaults.vertical_smoothing),
+            horizontal_smoothing: horizontal_smoothing.unwrap_or(defaults.horizontal_smoothing),
+            corner_smoothing: corner_smoothing.unwrap_or(defaults.corner_smoothing),
+
+            strict_mode: strict_mode.unwrap_or(defaults.strict_mode),
+            seam_split_position: seam_split_position.unwrap_or(defaults.seam_split_position),
+            seam_brightness_balance: seam_brightness_balance.unwrap_or(defaults.seam_brightness_balance),
+
+            blend_space: blend_space
+                .map(parse_blend_space)
+                .transpose()?
+                .unwrap_or(defaults.blend_space),
+            brightness_metric: brightness_metric
+                .map(parse_brightness_metric)
+                .transpose()?
+                .unwrap_or(defaults.brightness_metric),
+        })
+    }
+
+    #[classattr]
+    const PRESET_NAMES: [&'static str; 4] = ImplOptions::PRESET_NAMES;
+
+    #[staticmethod]
+    fn preset(name: &str) -> PyResult<Self> {
+        ImplOptions::preset(name)
+            .map(|options| MlaaOptions::from(&options))
+            .ok_or_else(|| {
+                PyValueError::new_err(format!(
+                    "Unknown preset '{}', expected one of {:?}",
+                    name,
+                    ImplOptions::PRESET_NAMES
+                ))
+            })
+    }
+
+    #[getter]
+    fn get_blend_space(&self) -> &'static str {
+        self.blend_space.name()
+    }
+
+    #[setter]
+    fn set_blend_space(&mut self, name: &str) -> PyResult<()> {
+        self.blend_space = parse_blend_space(name)?;
+        Ok(())
+    }
+
+    #[getter]
+    fn get_brightness_metric(&self) -> &'static str {
+        self.brightness_metric.name()
+    }
+
+    #[setter]
+    fn set_brightness_metric(&mut self, name: &str) -> PyResult<()> {
+        self.brightness_metric = parse_brightness_metric(name)?;
+        Ok(())
+    }
+
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            concat!(
+                "MlaaOptions(vertical_smoothing={}, horizontal_smoothing={}, corner_smoothing={}, ",
+                "strict_mode={}, seam_split_position={:?}, seam_brightness_balance={}, ",
+                "blend_space='{}', brightness_metric='{}')"
+            ),
+            python_bool(self.vertical_smoothing),
+            python_bool(self.horizontal_smoothing),
+            python_bool(self.corner_smoothing),
+            python_bool(self.strict_mode),
+            self.seam_split_position,
+            python_bool(self.seam_brightness_balance),
+            self.blend_space.name(),
+            self.brightness_metric.name(),
+        )
+    }
+}
+
+/// An edge found by `features()`. `kind` is "vertical_gradient",
+/// "horizontal_gradient" or "corner", `length` is the size of a gradient along
+/// its edge and 0.0 for corners. The two RGBA colors are floats, linear for
+/// float32 arrays like the array itself.
+#[pyclass(frozen, get_all, module = "mlaa")]
+pub struct MlaaFeature {
+    kind: &'static str,
+    x: f32,
+    y: f32,
+    length: f32,
+    colors: ([f32; 4], [f32; 4]),
+}
+
+impl From<ImplFeature<[f32; 4]>> for MlaaFeature {
+    fn from(feature: ImplFeature<[f32; 4]>) -> Self {
+        let (kind, x, y, length, colors) = match feature {
+            ImplFeature::VerticalGradient { x, y, height, colors } => ("vertical_gradient", x, y, height, colors),
+            ImplFeature::HorizontalGradient { x, y, width, colors } => ("horizontal_gradient", x, y, width, colors),
+            ImplFeature::Corner { x, y, colors } => ("corner", x as f32, y as f32, 0.0, colors),
+        };
+
+        MlaaFeature {
+            kind,
+            x,
+            y,
+            length,
+            colors,
+        }
+    }
+}
+
+#[pymethods]
+impl MlaaFeature {
+    fn __repr__(&self) -> String {
+        format!(
+            "MlaaFeature(kind='{}', x={:?}, y={:?}, length={:?}, colors=({:?}, {:?}))",
+            self.kind, self.x, self.y, self.length, self.colors.0, self.colors.1
+        )
+    }
+}
+
+// Sample types of the accepted arrays, converted like the pixels of the
+// `image` crate. Float arrays hold linear light, integer ones are
+// sRGB-encoded.
+trait Sample: Channel + Element + Send + Sync {
+    const IS_LINEAR: bool;
+}
+
+impl Sample for u8 {
+    const IS_LINEAR: bool = false;
+}
+
+impl Sample for u16 {
+    const IS_LINEAR: bool = false;
+}
+
+impl Sample for f32 {
+    const IS_LINEAR: bool = true;
+}
+
+// H×W×C arrays, where C is 1 (gray), 2 (gray and alpha), 3 (RGB) or 4 (RGBA).
+#[derive(FromPyObject)]
+enum ImageArray<'py> {
+    #[pyo3(annotation = "numpy.ndarray[uint8]")]
+    U8(PyReadonlyArray3<'py, u8>),
+    #[pyo3(annotation = "numpy.ndarray[uint16]")]
+    U16(PyReadonlyArray3<'py, u16>),
+    #[pyo3(annotation = "numpy.ndarray[float32]")]
+    F32(PyReadonlyArray3<'py, f32>),
+}
+
+#[derive(FromPyObject)]
+enum ImageArrayMut<'py> {
+    #[pyo3(annotation = "numpy.ndarray[uint8]")]
+    U8(PyReadwriteArray3<'py, u8>),
+    #[pyo3(annotation = "numpy.ndarray[uint16]")]
+    U16(PyReadwriteArray3<'py, u16>),
+    #[pyo3(annotation = "numpy.ndarray[float32]")]
+    F32(PyReadwriteArray3<'py, f32>),
+}
+
+fn check_shape(shape: &[usize]) -> PyResult<()> {
+    if (1..=4).contains(&shape[2]) {
+        Ok(())
+    } else {
+        Err(PyValueError::new_err(format!(
+            "Expected an image array with 1, 2, 3 or 4 channels, got shape {:?}",
+            shape
+        )))
+    }
+}
+
+// Pixels of an H×W×C array, views with any strides are accepted.
+struct ArrayPixels<A>(A);
+
+impl<S, D> MlaaPixels for ArrayPixels<ArrayBase<D, Ix3>>
+where
+    S: Sample,
+    D: Data<Elem = S>,
+{
+    fn dimensions(&self) -> (usize, usize) {
+        let (height, width, _) = self.0.dim();
+        (width, height)
+    }
+
+    fn get_rgba(&self, x: usize, y: usize) -> [f32; 4] {
+        let channels = self.0.dim().2;
+        let mut samples = [S::DEFAULT_MIN_VALUE; 4];
+        for (channel, sample) in samples[..channels].iter_mut().enumerate() {
+            *sample = self.0[[y, x, channel]];
+        }
+
+        samples_to_rgba(&samples[..channels])
+    }
+}
+
+impl<S, D> MlaaPixelsMut for ArrayPixels<ArrayBase<D, Ix3>>
+where
+    S: Sample,
+    D: DataMut<Elem = S>,
+{
+    fn put_rgba(&mut self, x: usize, y: usize, c: [f32; 4]) {
+        let channels = self.0.dim().2;
+        let mut samples = [S::DEFAULT_MIN_VALUE; 4];
+        rgba_to_samples(c, &mut samples[..channels]);
+
+        for (channel, &sample) in samples[..channels].iter().enumerate() {
+            self.0[[y, x, channel]] = sample;
+        }
+    }
+}
+
+fn image_features<S: Sample>(image: ArrayView3<S>, options: &ImplOptions) -> Vec<ImplFeature<[f32; 4]>> {
+    if S::IS_LINEAR {
+        mlaa_linear_pixels_features(&ArrayPixels(image), options)
+    } else {
+        mlaa_pixels_features(&ArrayPixels(image), options)
+    }
+}
+
+fn image_process<S: Sample>(image: ArrayViewMut3<S>, options: &ImplOptions) {
+    if S::IS_LINEAR {
+        mlaa_linear_pixels_in_place(&mut ArrayPixels(image), options);
+    } else {
+        mlaa_pixels_in_place(&mut ArrayPixels(image), options);
+    }
+}
+
+fn image_process_copy<'py, S: Sample>(
+    py: Python<'py>,
+    image: ArrayView3<S>,
+    options: &ImplOptions,
+) -> Bound<'py, PyAny> {
+    let mut result: Array3<S> = image.to_owned();
+    py.detach(|| image_process(result.view_mut(), options));
+    PyArray3::from_owned_array(py, result).into_any()
+}
+
+fn checked_options(options: Option<PyRef<MlaaOptions>>) -> PyResult<ImplOptions> {
+    let options = options.map(|options| ImplOptions::from(&*options)).unwrap_or_default();
+    options.validate().map_err(PyValueError::new_err)?;
+    Ok(options)
+}
+
+#[pyfunction]
+#[pyo3(signature = (image, options = None))]
+fn features(py: Python<'_>, image: ImageArray, options: Option<PyRef<MlaaOptions>>) -> PyResult<Vec<MlaaFeature>> {
+    let options = checked_options(options)?;
+
+    let features = match &image {
+        ImageArray::U8(image) => {
+            check_shape(image.shape())?;
+            let image = image.as_array();
+            py.detach(|| image_features(image, &options))
+        }
+        ImageArray::U16(image) => {
+            check_shape(image.shape())?;
+            let image = image.as_array();
+            py.detach(|| image_features(image, &options))
+        }
+        ImageArray::F32(image) => {
+            check_shape(image.shape())?;
+            let image = image.as_array();
+            py.detach(|| image_features(image, &options))
+        }
+    };
+
+    Ok(features.into_iter().map(MlaaFeature::from).collect())
+}
+
+#[pyfunction]
+#[pyo3(signature = (image, options = None))]
+fn process<'py>(
+    py: Python<'py>,
+    image: ImageArray<'py>,
+    options: Option<PyRef<MlaaOptions>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let options = checked_options(options)?;
+
+    match &image {
+        ImageArray::U8(image) => {
+            check_shape(image.shape())?;
+            Ok(image_process_copy(py, image.as_array(), &options))
+        }
+        ImageArray::U16(image) => {
+            check_shape(image.shape())?;
+            Ok(image_process_copy(py, image.as_array(), &options))
+        }
+        ImageArray::F32(image) => {
+            check_shape(image.shape())?;
+            Ok(image_process_copy(py, image.as_array(), &options))
+        }
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (image, options = None))]
+fn process_in_place(py: Python<'_>, image: ImageArrayMut, options: Option<PyRef<MlaaOptions>>) -> PyResult<()> {
+    let options = checked_options(options)?;
+
+    match image {
+        ImageArrayMut::U8(mut image) => {
+            check_shape(image.shape())?;
+            let image = image.as_array_mut();
+            py.detach(|| image_process(image, &options));
+        }
+        ImageArrayMut::U16(mut image) => {
+            check_shape(image.shape())?;
+            let image = image.as_array_mut();
+            py.detach(|| image_process(image, &options));
+        }
+        ImageArrayMut::F32(mut image) => {
+            check_shape(image.shape())?;
+            let image = image.as_array_mut();
+            py.detach(|| image_process(image, &options));
+        }
+    }
+
+    Ok(())
+}
+
+#[pymodule(name = "mlaa")]
+fn mlaa_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    // Fails with an ImportError up front instead of a panic on the first
+    // array argument when NumPy is missing.
+    module.py().import("numpy")?;
+
+    module.add_class::<MlaaOptions>()?;
+    module.add_class::<MlaaFeature>()?;
+    module.add_function(wrap_pyfunction!(features, module)?)?;
+    module.add_function(wrap_pyfunction!(process, module)?)?;
+    module.add_function(wrap_pyfunction!(process_in_place, module)?)?;
+    Ok(())
+}
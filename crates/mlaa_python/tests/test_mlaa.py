@@ -0,0 +1,112 @@
+import copy
+import unittest
+
+import numpy as np
+
+import mlaa
+
+SIZE = 8
+
+
+# Diagonal split into a dark upper left and a bright lower right half, opaque
+# when there is an alpha channel.
+def staircase(dtype, channels, white):
+    y, x = np.mgrid[0:SIZE, 0:SIZE]
+    image = np.where((x + y) < SIZE, 0, white).astype(dtype)
+    image = np.repeat(image[:, :, np.newaxis], channels, axis=2)
+    if channels in (2, 4):
+        image[:, :, -1] = white
+    return image
+
+
+def is_blended(image, white):
+    return bool(np.any((image > 0) & (image < white)))
+
+
+def srgb_to_linear(v):
+    return np.where(v <= 0.04045, v / 12.92, ((v + 0.055) / 1.055) ** 2.4)
+
+
+class OptionsTest(unittest.TestCase):
+    def test_defaults(self):
+        options = mlaa.MlaaOptions()
+        self.assertTrue(options.vertical_smoothing)
+        self.assertIn(options.blend_space, ["srgb", "linear", "oklab", "cielab"])
+        self.assertEqual(options, copy.copy(options))
+
+    def test_keywords(self):
+        options = mlaa.MlaaOptions(blend_space="oklab", seam_split_position=0.25)
+        self.assertEqual(options.blend_space, "oklab")
+        self.assertEqual(options.seam_split_position, 0.25)
+        self.assertNotEqual(options, mlaa.MlaaOptions())
+
+        with self.assertRaises(ValueError):
+            mlaa.MlaaOptions(blend_space="hsv")
+        with self.assertRaises(ValueError):
+            options.brightness_metric = "hsv"
+
+    def test_presets(self):
+        for name in mlaa.MlaaOptions.PRESET_NAMES:
+            self.assertIsInstance(mlaa.MlaaOptions.preset(name), mlaa.MlaaOptions)
+        with self.assertRaises(ValueError):
+            mlaa.MlaaOptions.preset("no-such-preset")
+
+
+class ImageTest(unittest.TestCase):
+    def test_features(self):
+        features = mlaa.features(staircase(np.uint8, 4, 255))
+        self.assertGreater(len(features), 0)
+
+        kinds = {feature.kind for feature in features}
+        self.assertTrue(kinds <= {"vertical_gradient", "horizontal_gradient", "corner"})
+
+        for feature in features:
+            self.assertEqual(len(feature.colors), 2)
+            self.assertEqual(len(feature.colors[0]), 4)
+
+    def test_process(self):
+        for dtype, white in [(np.uint8, 255), (np.uint16, 65535), (np.float32, 1.0)]:
+            for channels in [1, 2, 3, 4]:
+                with self.subTest(dtype=dtype, channels=channels):
+                    image = staircase(dtype, channels, white)
+                    result = mlaa.process(image)
+                    self.assertEqual(result.dtype, image.dtype)
+                    self.assertEqual(result.shape, image.shape)
+                    self.assertTrue(is_blended(result, white))
+                    self.assertFalse(is_blended(image, white))
+
+    def test_float_arrays_are_linear(self):
+        # The same edge, once as sRGB-encoded bytes and once as linear floats
+        srgb_result = mlaa.process(staircase(np.uint8, 4, 255))
+        linear_result = mlaa.process(staircase(np.float32, 4, 1.0))
+        np.testing.assert_allclose(linear_result, srgb_to_linear(srgb_result / 255.0), atol=0.01)
+
+        feature_colors = [
+            color for feature in mlaa.features(staircase(np.float32, 3, 4.0)) for color in feature.colors
+        ]
+        self.assertAlmostEqual(max(color[0] for color in feature_colors), 4.0, places=4)
+
+    def test_process_in_place(self):
+        image = staircase(np.uint8, 4, 255)
+        expected = mlaa.process(image, mlaa.MlaaOptions.preset("soft"))
+        mlaa.process_in_place(image, mlaa.MlaaOptions.preset("soft"))
+        np.testing.assert_array_equal(image, expected)
+
+    def test_strided_view(self):
+        padded = np.zeros((SIZE, SIZE + 3, 4), dtype=np.uint8)
+        padded[:, :SIZE] = staircase(np.uint8, 4, 255)
+        mlaa.process_in_place(padded[:, :SIZE])
+        self.assertTrue(is_blended(padded[:, :SIZE], 255))
+        self.assertFalse(padded[:, SIZE:].any())
+
+    def test_invalid_arguments(self):
+        with self.assertRaises(TypeError):
+            mlaa.process(staircase(np.float64, 4, 1.0))
+        with self.assertRaises(ValueError):
+            mlaa.process(np.zeros((SIZE, SIZE, 5), dtype=np.uint8))
+        with self.assertRaises(ValueError):
+            mlaa.process(staircase(np.uint8, 4, 255), mlaa.MlaaOptions(seam_split_position=2.0))
+
+
+if __name__ == "__main__":
+    unittest.main()
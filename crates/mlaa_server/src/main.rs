@@ -0,0 +1,318 @@
+use std::error::Error;
+use std::io::{Cursor, Read};
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use clap::Parser;
+use image::ImageFormat;
+use serde_json::json;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::options::resolve_options;
+use mlaa_impl::{encode_dynamic_image, mlaa_dynamic_image};
+
+use crate::process::{decode_image, features_json};
+
+mod options;
+mod process;
+
+// Header carrying the MLAA options as a JSON object, the request body is the
+// image itself.
+const OPTIONS_HEADER: &str = "X-Mlaa-Options";
+
+#[derive(Parser)]
+#[command(version)]
+struct ServerArgs {
+    /// Address to listen on
+    #[clap(long = "bind", default_value = "127.0.0.1:8080")]
+    bind: SocketAddr,
+
+    /// Number of requests processed at the same time, defaults to the number of CPUs
+    #[clap(long = "max-concurrent")]
+    max_concurrent: Option<NonZeroUsize>,
+
+    /// Largest accepted request body in bytes
+    #[clap(long = "max-body-size", default_value_t = 64 * 1024 * 1024)]
+    max_body_size: usize,
+}
+
+struct HttpError {
+    status_code: u16,
+    message: String,
+}
+
+impl HttpError {
+    fn new(status_code: u16, message: impl ToString) -> HttpError {
+        HttpError {
+            status_code,
+            message: message.to_string(),
+        }
+    }
+}
+
+fn bad_request(err: Box<dyn Error>) -> HttpError {
+    HttpError::new(400, err)
+}
+
+fn content_type(value: &str) -> Header {
+    Header::from_bytes("Content-Type", value).unwrap()
+}
+
+fn read_body(request: &mut Request, max_body_size: usize) -> Result<Vec<u8>, HttpError> {
+    let too_large = || HttpError::new(413, format!("Request body exceeds {} bytes", max_body_size));
+
+    if request
+        .body_length()
+        .is_some_and(|body_length| body_length > max_body_size)
+    {
+        return Err(too_large());
+    }
+
+    // Chunked bodies have no length up front, reading stops right after the
+    // limit.
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(max_body_size as u64 + 1)
+        .read_to_end(&mut body)
+        .map_err(|err| HttpError::new(400, err))?;
+
+    if body.len() > max_body_size {
+        return Err(too_large());
+    }
+
+    Ok(body)
+}
+
+fn handle_request(request: &mut Request, args: &ServerArgs) -> Result<Response<Cursor<Vec<u8>>>, HttpError> {
+    let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+    let path = path.to_owned();
+    let query_parameters = form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect::<Vec<_>>();
+
+    if !matches!(path.as_str(), "/process" | "/features") {
+        return Err(HttpError::new(404, format!("Unknown path \"{}\"", path)));
+    }
+    if *request.method() != Method::Post {
+        return Err(HttpError::new(405, format!("{} only accepts POST requests", path)));
+    }
+
+    let json_options = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(OPTIONS_HEADER))
+        .map(|header| header.value.to_string());
+    let mlaa_options = resolve_options(json_options.as_deref(), &query_parameters).map_err(bad_request)?;
+
+    let body = read_body(request, args.max_body_size)?;
+    let (input_image, input_format) = decode_image(&body).map_err(bad_request)?;
+
+    if path == "/features" {
+        let features = features_json(&input_image, &mlaa_options);
+        return Ok(Response::from_data(features.to_string()).with_header(content_type("application/json")));
+    }
+
+    let output_format = match query_parameters.iter().find(|(key, _)| key == "format") {
+        Some((_, format_name)) => ImageFormat::from_extension(format_name)
+            .ok_or_else(|| HttpError::new(400, format!("Unknown image format \"{}\"", format_name)))?,
+        None => input_format,
+    };
+
+    let output_image = mlaa_dynamic_image(&input_image, &mlaa_options);
+    let output_data = encode_dynamic_image(output_image, output_format).map_err(|err| HttpError::new(400, err))?;
+
+    Ok(Response::from_data(output_data).with_header(content_type(output_format.to_mime_type())))
+}
+
+fn run_worker(server: &Server, args: &ServerArgs) {
+    loop {
+        let mut request = match server.recv() {
+            Ok(request) => request,
+            Err(err) => {
+                eprintln!("mlaa_server: {}", err);
+                continue;
+            }
+        };
+
+        let start_time = Instant::now();
+        let (method, url) = (request.method().clone(), request.url().to_owned());
+
+        let response = handle_request(&mut request, args).unwrap_or_else(|err| {
+            Response::from_data(json!({ "error": err.message }).to_string())
+                .with_status_code(err.status_code)
+                .with_header(content_type("application/json"))
+        });
+        let status_code = response.status_code().0;
+
+        if let Err(err) = request.respond(response) {
+            eprintln!("mlaa_server: {}", err);
+        }
+
+        eprintln!(
+            "mlaa_server: {} {} {} ({} ms)",
+            method,
+            url,
+            status_code,
+            start_time.elapsed().as_millis()
+        );
+    }
+}
+
+// Every worker handles one request at a time, further requests wait until a
+// worker is free.
+fn spawn_workers(server: &Arc<Server>, args: &Arc<ServerArgs>, worker_count: usize) -> Vec<JoinHandle<()>> {
+    (0..worker_count)
+        .map(|_| {
+            let (server, args) = (server.clone(), args.clone());
+            thread::spawn(move || run_worker(&server, &args))
+        })
+        .collect()
+}
+
+// cargo run --release --bin mlaa_server -- --bind 127.0.0.1:8080
+// curl --data-binary @test/input.png "http://127.0.0.1:8080/process?preset=soft" -o test/output.png
+
+fn main() -> ExitCode {
+    match main_inner() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("mlaa_server: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn main_inner() -> Result<(), Box<dyn Error>> {
+    let args = Arc::new(ServerArgs::parse());
+
+    let worker_count = args
+        .max_concurrent
+        .or_else(|| thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get);
+
+    let server = Arc::new(Server::http(args.bind).map_err(|err| err.to_string())?);
+    eprintln!(
+        "mlaa_server: Listening on http://{} with {} workers",
+        server.server_addr(),
+        worker_count
+    );
+
+    for worker in spawn_workers(&server, &args, worker_count) {
+        worker.join().map_err(|_| "Worker thread panicked")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::net::TcpStream;
+
+    use image::{DynamicImage, RgbaImage};
+    use mlaa_impl::MlaaOptions;
+    use serde_json::Value;
+
+    use super::*;
+
+    // A server on an ephemeral port, the worker threads are left running
+    // until the test binary exits.
+    fn start_server() -> SocketAddr {
+        let args = Arc::new(ServerArgs::parse_from(["mlaa_server", "--bind", "127.0.0.1:0"]));
+        let server = Arc::new(Server::http(args.bind).unwrap());
+        let server_addr = server.server_addr().to_ip().unwrap();
+
+        spawn_workers(&server, &args, 1);
+        server_addr
+    }
+
+    // Returns the status code, the content type and the body of the response.
+    fn post(server_addr: SocketAddr, path: &str, body: &[u8]) -> (u16, String, Vec<u8>) {
+        let mut stream = TcpStream::connect(server_addr).unwrap();
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            path,
+            server_addr,
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(body).unwrap();
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+
+        let header_length = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let headers = String::from_utf8(response[..header_length].to_vec()).unwrap();
+        let status_code = headers.split(' ').nth(1).unwrap().parse().unwrap();
+        let content_type = headers
+            .lines()
+            .find_map(|line| line.strip_prefix("Content-Type: "))
+            .unwrap_or_default()
+            .to_owned();
+
+        (status_code, content_type, response[header_length + 4..].to_vec())
+    }
+
+    fn staircase_png() -> (DynamicImage, Vec<u8>) {
+        let image = DynamicImage::from(RgbaImage::from_fn(8, 8, |x, y| {
+            if x + y < 8 {
+                image::Rgba([0, 0, 0, 255])
+            } else {
+                image::Rgba([255; 4])
+            }
+        }));
+        let image_data = encode_dynamic_image(image.clone(), ImageFormat::Png).unwrap();
+        (image, image_data)
+    }
+
+    #[test]
+    fn process_returns_the_antialiased_image() {
+        let server_addr = start_server();
+        let (input_image, input_data) = staircase_png();
+
+        let (status_code, content_type, body) = post(server_addr, "/process?preset=soft", &input_data);
+        assert_eq!(status_code, 200);
+        assert_eq!(content_type, "image/png");
+
+        let output_image = image::load_from_memory_with_format(&body, ImageFormat::Png).unwrap();
+        let mlaa_options = MlaaOptions::preset("soft").unwrap();
+        assert_eq!(output_image, mlaa_dynamic_image(&input_image, &mlaa_options));
+        assert_ne!(output_image, input_image);
+
+        let (status_code, content_type, body) = post(server_addr, "/process?format=bmp", &input_data);
+        assert_eq!(status_code, 200);
+        assert_eq!(content_type, "image/bmp");
+        assert!(image::load_from_memory_with_format(&body, ImageFormat::Bmp).is_ok());
+    }
+
+    #[test]
+    fn features_are_returned_as_json() {
+        let server_addr = start_server();
+        let (_, input_data) = staircase_png();
+
+        let (status_code, content_type, body) = post(server_addr, "/features", &input_data);
+        assert_eq!(status_code, 200);
+        assert_eq!(content_type, "application/json");
+
+        let features = serde_json::from_slice::<Value>(&body).unwrap();
+        assert_eq!(features["width"], 8);
+        assert!(!features["features"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_requests_are_rejected() {
+        let server_addr = start_server();
+        let (_, input_data) = staircase_png();
+
+        assert_eq!(post(server_addr, "/process", b"not an image").0, 400);
+        assert_eq!(post(server_addr, "/process?preset=unknown", &input_data).0, 400);
+        assert_eq!(post(server_addr, "/process?format=xyz", &input_data).0, 400);
+        assert_eq!(post(server_addr, "/unknown", &input_data).0, 404);
+    }
+}
@@ -0,0 +1,71 @@
+use std::error::Error;
+
+use serde_json::{Map, Value};
+
+use mlaa_impl::MlaaOptions;
+
+// Same as in the config files of mlaa_image.
+const OPTION_ALIASES: [(&str, &str); 1] = [("seam_brightness_balance", "seam_brigtness_balance")];
+
+// Query parameters which are not MLAA options.
+pub const RESERVED_PARAMETERS: [&str; 1] = ["format"];
+
+fn option_name(key: &str) -> &str {
+    OPTION_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == key)
+        .map_or(key, |(_, name)| name)
+}
+
+// Query values are parsed as JSON where possible, so `true` and `0.5` become
+// a boolean and a number, anything else is kept as a string.
+fn query_value(value: &str) -> Value {
+    serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_owned()))
+}
+
+// The options are resolved from the preset, then the JSON options, then the
+// query parameters, the later ones overriding the earlier ones.
+pub fn resolve_options(
+    json_options: Option<&str>,
+    query_parameters: &[(String, String)],
+) -> Result<MlaaOptions, Box<dyn Error>> {
+    let mut values = Map::new();
+
+    if let Some(json_options) = json_options {
+        match serde_json::from_str(json_options)? {
+            Value::Object(json_values) => values.extend(json_values),
+            _ => return Err("JSON options must be an object".into()),
+        }
+    }
+
+    for (key, value) in query_parameters {
+        if !RESERVED_PARAMETERS.contains(&key.as_str()) {
+            values.insert(key.clone(), query_value(value));
+        }
+    }
+
+    let base_options = match values.remove("preset") {
+        Some(Value::String(preset_name)) => MlaaOptions::preset(&preset_name).ok_or_else(|| {
+            format!(
+                "Unknown preset \"{}\", expected one of {}",
+                preset_name,
+                MlaaOptions::PRESET_NAMES.join(", ")
+            )
+        })?,
+        Some(_) => return Err("`preset` must be a string".into()),
+        None => MlaaOptions::default(),
+    };
+
+    let Value::Object(mut option_values) = serde_json::to_value(base_options)? else {
+        unreachable!();
+    };
+
+    for (key, value) in values {
+        option_values.insert(option_name(&key).to_owned(), value);
+    }
+
+    let options: MlaaOptions = serde_json::from_value(Value::Object(option_values))?;
+    options.validate()?;
+
+    Ok(options)
+}
@@ -0,0 +1,65 @@
+use std::error::Error;
+use std::io::Cursor;
+
+use image::io::Reader as ImageReader;
+use image::{DynamicImage, ImageFormat};
+use serde_json::{json, Value};
+
+use mlaa_impl::{mlaa_features, pixel_to_rgba, MlaaFeature, MlaaOptions};
+
+// Decoding uses the default limits of the image crate, which guards against
+// images expanding to huge allocations from small request bodies.
+pub fn decode_image(image_data: &[u8]) -> Result<(DynamicImage, ImageFormat), Box<dyn Error>> {
+    let reader = ImageReader::new(Cursor::new(image_data)).with_guessed_format()?;
+    let image_format = reader.format().ok_or("Unable to detect the image format")?;
+    Ok((reader.decode()?, image_format))
+}
+
+pub fn features_json(image: &DynamicImage, mlaa_options: &MlaaOptions) -> Value {
+    let image = image.to_rgba32f();
+    let mut features = Vec::new();
+
+    mlaa_features(
+        image.width() as usize,
+        image.height() as usize,
+        |x, y| {
+            image
+                .get_pixel_checked(x as u32, y as u32)
+                .map(pixel_to_rgba)
+                .unwrap_or([0.0; 4])
+        },
+        |c| mlaa_options.brightness_metric.brightness(c),
+        mlaa_options,
+        |mlaa_feature| {
+            features.push(match mlaa_feature {
+                MlaaFeature::VerticalGradient { x, y, height, colors } => json!({
+                    "kind": "vertical_gradient",
+                    "x": x,
+                    "y": y,
+                    "length": height,
+                    "colors": [colors.0, colors.1],
+                }),
+                MlaaFeature::HorizontalGradient { x, y, width, colors } => json!({
+                    "kind": "horizontal_gradient",
+                    "x": x,
+                    "y": y,
+                    "length": width,
+                    "colors": [colors.0, colors.1],
+                }),
+                MlaaFeature::Corner { x, y, colors } => json!({
+                    "kind": "corner",
+                    "x": x,
+                    "y": y,
+                    "length": 0.0,
+                    "colors": [colors.0, colors.1],
+                }),
+            })
+        },
+    );
+
+    json!({
+        "width": image.width(),
+        "height": image.height(),
+        "features": features,
+    })
+}